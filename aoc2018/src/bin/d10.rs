@@ -1,7 +1,7 @@
 use anyhow::Result;
 use itertools::{chain, Itertools};
 use std::io::{stdin, BufRead};
-use utils::{Direction, Grid, Point};
+use utils::{ocr, Direction, Grid, Point};
 
 struct TestCase {
     points: Vec<(Point, Direction)>,
@@ -44,6 +44,9 @@ impl TestCase {
             grid[p - Point(min_x, min_y) + Point(1, 1)] = '#';
         }
         println!("{t}\n{grid}");
+        if let Some(text) = ocr::read(&grid) {
+            println!("{text}");
+        }
     }
 }
 
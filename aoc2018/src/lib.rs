@@ -1,3 +1,4 @@
 mod grid;
+pub mod ocr;
 
 pub use grid::*;
@@ -0,0 +1,56 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use utils::Intcode;
+
+const PROGRAMS: &[(&str, &str, &[isize])] = &[
+    ("input_output", "3,9,1002,9,3,9,4,9,99,0", &[33]),
+    ("teq", "3,9,8,9,10,9,4,9,99,-1,8", &[8]),
+    ("jump", "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[1]),
+    (
+        "large_program",
+        "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,\
+         1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        &[8],
+    ),
+    ("quine", "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99", &[]),
+    ("bignum", "1102,34915192,34915192,7,4,7,99,0", &[]),
+];
+
+// Counts down from its input, so the work is all in one hot loop.
+const COUNTDOWN: &str = r"
+    in [n]
+loop:
+    add [n], -1, [n]
+    jit [n], loop
+    out [n]
+    hlt
+n:  .data 0
+";
+
+fn bench(program: &Intcode, input: &[isize]) -> Duration {
+    let start = Instant::now();
+    let mut iters = 0;
+    while start.elapsed() < Duration::from_millis(300) {
+        black_box(program.clone().run(input.iter().copied()).count());
+        iters += 1;
+    }
+    start.elapsed() / iters
+}
+
+fn compare(name: &str, program: &Intcode, input: &[isize]) {
+    let mut interpreted = program.clone();
+    interpreted.code.set_decode_cache(false);
+    let mut predecoded = program.clone();
+    predecoded.predecode();
+    let a = bench(&interpreted, input);
+    let b = bench(&predecoded, input);
+    let speedup = a.as_secs_f64() / b.as_secs_f64();
+    println!("{name:<16} interpreted {a:>12.2?}  predecoded {b:>12.2?}  speedup {speedup:.2}x");
+}
+
+fn main() {
+    for &(name, program, input) in PROGRAMS {
+        compare(name, &Intcode::parse(program), input);
+    }
+    compare("countdown", &Intcode::assemble(COUNTDOWN).unwrap(), &[100_000]);
+}
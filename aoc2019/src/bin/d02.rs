@@ -16,6 +16,7 @@ fn part2(program: &Intcode, needle: isize) -> isize {
 }
 
 fn main() {
-    let program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
+    let mut program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
+    program.predecode();
     dbg!(part1(&program, 12, 2), part2(&program, 19690720));
 }
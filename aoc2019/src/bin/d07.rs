@@ -1,27 +1,32 @@
-use itertools::{chain, izip, Itertools};
+use itertools::Itertools;
 use std::io::{read_to_string, stdin};
-use std::iter::once;
-use std::sync::mpsc;
-use utils::Intcode;
+use utils::{Intcode, Network};
+
+/// Amplifiers in a chain, each feeding the next, with the last one optionally feeding the first.
+fn amplifiers(program: &Intcode, seq: [isize; 5], feedback: bool) -> isize {
+    let mut network = Network::new();
+    let amps = seq.map(|_| network.machine(program.clone()));
+    for (&a, s) in amps.iter().zip(seq) {
+        network.seed(a, [s]);
+    }
+    network.seed(amps[0], [0]);
+    for (i, (&a, &b)) in amps.iter().tuple_windows().enumerate() {
+        network.connect(&format!("amp{i}"), a, b);
+    }
+    if feedback {
+        network.connect("feedback", amps[4], amps[0]);
+    }
+    network.output("thrusters", amps[4]);
+    network.run().unwrap_or_else(|(_, e)| panic!("{e}"));
+    *network.observed("thrusters").last().unwrap()
+}
 
 fn run_setting(program: &Intcode, seq: [isize; 5]) -> isize {
-    let mut amp = vec![program.clone(); seq.len()];
-    let init: Box<dyn Iterator<Item = isize>> = Box::new(once(0));
-    izip!(amp.iter_mut(), seq)
-        .fold(init, |st, (a, s)| Box::new(a.run(chain![once(s), st])))
-        .last()
-        .unwrap()
+    amplifiers(program, seq, false)
 }
 
 fn run_feedback_setting(program: &Intcode, seq: [isize; 5]) -> isize {
-    let mut amp = vec![program.clone(); seq.len()];
-    let (tx, rx) = mpsc::channel();
-    let init: Box<dyn Iterator<Item = isize>> = Box::new(chain![once(0), rx]);
-    izip!(amp.iter_mut(), seq)
-        .fold(init, |st, (a, s)| Box::new(a.run(chain![once(s), st])))
-        .inspect(|&x| tx.send(x).unwrap())
-        .last()
-        .unwrap()
+    amplifiers(program, seq, true)
 }
 
 fn main() {
@@ -2,7 +2,7 @@ use itertools::Itertools;
 use std::collections::HashMap;
 use std::io::stdin;
 use std::iter::once;
-use utils::{Direction, Grid, Intcode, Point};
+use utils::{ocr, Direction, Grid, Intcode, Point};
 
 struct State {
     painted: HashMap<Point, isize>,
@@ -49,9 +49,14 @@ impl State {
         };
         grid
     }
+
+    /// The registration identifier painted in white, if it is readable.
+    fn identifier(&self) -> Option<String> {
+        ocr::read_points(self.painted.iter().filter(|(_, &t)| t == 1).map(|(&p, _)| p))
+    }
 }
 
-fn generate(program: &Intcode, init_tile: isize) {
+fn generate(program: &Intcode, init_tile: isize) -> State {
     let mut program = program.clone();
     let mut state = State::new();
     let deferred = program.deferred_run();
@@ -62,10 +67,30 @@ fn generate(program: &Intcode, init_tile: isize) {
     }
     println!("{}", state.draw());
     println!("Painted tiles: {}\n", state.painted.len());
+    state
 }
 
 fn main() {
     let program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
     generate(&program, 0);
-    generate(&program, 1);
+    let state = generate(&program, 1);
+    println!("Identifier: {}", state.identifier().as_deref().unwrap_or("unreadable"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier() {
+        let mut state = State::new();
+        for p in (0..6).map(|x| Point(x, 0)).chain((1..4).map(|y| Point(5, y))) {
+            state.painted.insert(p, 1);
+        }
+        // Painted back to black, so not part of the letter.
+        state.painted.insert(Point(0, 3), 0);
+        assert_eq!(state.identifier().as_deref(), Some("L"));
+        state.painted.insert(Point(2, 2), 1);
+        assert_eq!(state.identifier(), None);
+    }
 }
@@ -1,8 +1,14 @@
 use itertools::Itertools;
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::io::{stdin, stdout};
-use utils::{terminal, Direction, Grid, Intcode, Point};
+use std::env;
+use std::fs::{read_to_string, File};
+use std::io::{stdin, stdout, BufRead, BufReader, Read, Write};
+use std::time::Duration;
+use utils::terminal::{self, Key, Keys, RawMode};
+use utils::{Direction, Event, Grid, Intcode, Point};
+
+/// Separates the frames in a recording.
+const FRAME_END: &str = "--";
 
 #[derive(Default)]
 struct State {
@@ -19,17 +25,16 @@ impl State {
         }
     }
 
-    fn should_move(&self) -> isize {
-        let ball = self.entities.iter().find(|(_, &t)| t == 4).unwrap().0 .1;
-        let bar = self.entities.iter().find(|(_, &t)| t == 3).unwrap().0 .1;
-        (ball - bar).signum()
+    /// The column of the first tile of type `t`.
+    fn find(&self, t: isize) -> Option<isize> {
+        self.entities.iter().find(|(_, &e)| e == t).map(|(p, _)| p.1)
     }
 
-    fn print(&self) {
-        let minx = self.entities.keys().map(|p| p.0).min().unwrap();
-        let maxx = self.entities.keys().map(|p| p.0).max().unwrap();
-        let miny = self.entities.keys().map(|p| p.1).min().unwrap();
-        let maxy = self.entities.keys().map(|p| p.1).max().unwrap();
+    fn render(&self) -> String {
+        let minx = self.entities.keys().map(|p| p.0).min().unwrap_or(0);
+        let maxx = self.entities.keys().map(|p| p.0).max().unwrap_or(-1);
+        let miny = self.entities.keys().map(|p| p.1).min().unwrap_or(0);
+        let maxy = self.entities.keys().map(|p| p.1).max().unwrap_or(-1);
         let offset = Direction::new(-minx, -miny);
         let mut grid = Grid::new(' ', (maxx - minx + 1) as usize, (maxy - miny + 1) as usize);
         for (&p, &t) in self.entities.iter() {
@@ -42,9 +47,90 @@ impl State {
                 _ => panic!("Unexpected entity {t}"),
             };
         }
-        terminal::home(stdout());
-        println!("{grid}Score: {}", self.score);
-        std::thread::sleep(std::time::Duration::from_millis(2));
+        format!("{grid}Score: {}\n", self.score)
+    }
+}
+
+/// Decides which way to tilt the joystick: -1 for left, 0 for neutral, 1 for right.
+trait Joystick {
+    fn tilt(&mut self, state: &State) -> isize;
+}
+
+/// Keeps the paddle under the ball.
+struct Tracker;
+
+impl Joystick for Tracker {
+    fn tilt(&mut self, state: &State) -> isize {
+        match (state.find(4), state.find(3)) {
+            (Some(ball), Some(paddle)) => (ball - paddle).signum(),
+            _ => 0,
+        }
+    }
+}
+
+/// Reads a key per move: `a` or left tilts left, `d` or right tilts right, anything else keeps
+/// the joystick neutral.
+struct Keyboard<R>(Keys<R>);
+
+impl<R: Read> Joystick for Keyboard<R> {
+    fn tilt(&mut self, _: &State) -> isize {
+        match self.0.next_key().expect("failed to read the keyboard") {
+            Some(Key::Char('a') | Key::Left) => -1,
+            Some(Key::Char('d') | Key::Right) => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Plays the game to the end and returns the final score. `frame` sees the screen whenever
+/// the game waits for the joystick, and once more at the end.
+fn play(
+    program: &mut Intcode,
+    joystick: &mut dyn Joystick,
+    mut frame: impl FnMut(&State),
+) -> isize {
+    let mut state = State::default();
+    let mut tile = vec![];
+    loop {
+        match program.resume().unwrap_or_else(|e| panic!("{e}")) {
+            Event::Output(x) => {
+                tile.push(x);
+                if let &[x, y, t] = &tile[..] {
+                    state.update(x, y, t);
+                    tile.clear();
+                }
+            }
+            Event::NeedsInput => {
+                frame(&state);
+                program.push_input(joystick.tilt(&state));
+            }
+            _ => {
+                frame(&state);
+                return state.score;
+            }
+        }
+    }
+}
+
+fn show(frame: &str) {
+    terminal::home(stdout());
+    print!("{frame}");
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+/// Shows the frames recorded in `r`.
+fn playback(r: impl BufRead) {
+    terminal::clear(stdout());
+    let mut frame = String::new();
+    for line in r.lines() {
+        let line = line.unwrap();
+        if line == FRAME_END {
+            show(&frame);
+            frame.clear();
+        } else {
+            frame += &line;
+            frame.push('\n');
+        }
     }
 }
 
@@ -52,17 +138,99 @@ fn part1(program: &Intcode) -> usize {
     program.clone().run(std::iter::empty()).tuples().filter(|&(_, _, t)| t == 2).count()
 }
 
+const USAGE: &str = "\
+Usage: d13 [options] [program]
+
+Reads the program from stdin if no file is given.
+
+Options:
+  --headless         don't draw the screen, only print the final score
+  --human            steer with `a` and `d` or the arrow keys, read from the terminal
+  --record <file>    write every frame to file
+  --playback <file>  show the frames recorded in file and exit";
+
 fn main() {
-    let mut program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
+    let mut args = env::args().skip(1);
+    let (mut headless, mut human, mut record, mut path) = (false, false, None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => headless = true,
+            "--human" => human = true,
+            "--record" => record = Some(args.next().expect(USAGE)),
+            "--playback" => {
+                return playback(BufReader::new(File::open(args.next().expect(USAGE)).unwrap()))
+            }
+            "-h" | "--help" => return println!("{USAGE}"),
+            _ => path = Some(arg),
+        }
+    }
+    let source = match path {
+        Some(path) => read_to_string(path).unwrap(),
+        None => stdin().lines().next().unwrap().unwrap(),
+    };
+    let mut program: Intcode = source.trim().parse().unwrap();
     dbg!(part1(&program));
 
     program.code[0] = 2;
-    let state = RefCell::new(State::default());
-    let joystick = std::iter::repeat_with(|| state.borrow().should_move());
-    terminal::clear(stdout());
-    for (x, y, t) in program.run(joystick).tuples() {
-        let mut state = state.borrow_mut();
-        state.update(x, y, t);
-        state.print();
+    let mut joystick: Box<dyn Joystick> = if human {
+        // Stdin may well be the program, so read the keys from the terminal itself.
+        let tty = RawMode::enable(File::open("/dev/tty").unwrap()).unwrap();
+        Box::new(Keyboard(Keys::new(tty)))
+    } else {
+        Box::new(Tracker)
+    };
+    let mut recording = record.map(|path| File::create(path).unwrap());
+    if !headless {
+        terminal::clear(stdout());
+    }
+    let score = play(&mut program, joystick.as_mut(), |state| {
+        if recording.is_none() && headless {
+            return;
+        }
+        let frame = state.render();
+        if let Some(file) = recording.as_mut() {
+            writeln!(file, "{frame}{FRAME_END}").unwrap();
+        }
+        if !headless {
+            show(&frame);
+        }
+    });
+    dbg!(score);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws a wall, a paddle and a ball, reads the joystick, then reports its input as the score.
+    const GAME: &str = r"
+        out 0
+        out 0
+        out 1
+        out 1
+        out 0
+        out 3
+        out 3
+        out 0
+        out 4
+        in [x]
+        out -1
+        out 0
+        out [x]
+        hlt
+    x:  .data 0
+    ";
+
+    #[test]
+    fn test_play() {
+        let mut frames = vec![];
+        let mut program = Intcode::assemble(GAME).unwrap();
+        let score = play(&mut program, &mut Tracker, |state| frames.push(state.render()));
+        assert_eq!(score, 1);
+        assert_eq!(frames, ["#= @\nScore: 0\n", "#= @\nScore: 1\n"]);
+
+        let mut program = Intcode::assemble(GAME).unwrap();
+        let mut keyboard = Keyboard(Keys::new("a\n".as_bytes()));
+        assert_eq!(play(&mut program, &mut keyboard, |_| {}), -1);
     }
 }
@@ -1,9 +1,11 @@
 use itertools::Itertools;
 use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs::read_to_string;
 use std::io::{stdin, stdout};
 use utils::{terminal, Deferred, Direction, Grid, Intcode, Point};
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 #[repr(u8)]
 enum Entity {
     Empty = b'.',
@@ -11,11 +13,24 @@ enum Entity {
     Wall = b'#',
 }
 
+/// Whatever the drone explores. Moving into a wall leaves the drone where it was.
+trait Environment {
+    /// Tries to move one step in direction `d`, and reports what is there.
+    fn try_move(&mut self, d: Direction) -> Entity;
+}
+
 struct Drone<'a>(Deferred<'a>);
 
-impl Drone<'_> {
-    fn try_move(&mut self, d: isize) -> Entity {
-        self.0.send(d);
+impl Environment for Drone<'_> {
+    fn try_move(&mut self, d: Direction) -> Entity {
+        let command = match d {
+            Direction::UP => 1,
+            Direction::DOWN => 2,
+            Direction::LEFT => 3,
+            Direction::RIGHT => 4,
+            _ => panic!("Unexpected direction {d:?}"),
+        };
+        self.0.send(command);
         match self.0.iter().next() {
             Some(0) => Entity::Wall,
             Some(1) => Entity::Empty,
@@ -25,18 +40,68 @@ impl Drone<'_> {
     }
 }
 
-struct State<'a> {
-    drone: Drone<'a>,
+/// A maze drawn in ASCII, with `D` where the drone starts. Anything outside is wall.
+struct Maze {
+    grid: Grid<Entity>,
+    pos: Point,
+}
+
+impl Maze {
+    fn parse(s: &str) -> Self {
+        let mut pos = None;
+        let rows = s.lines().map(|l| l.bytes().collect_vec()).collect_vec();
+        let w = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let grid = rows
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                (0..w)
+                    .map(|x| match row.get(x).copied().unwrap_or(b' ') {
+                        b'.' => Entity::Empty,
+                        b'O' => Entity::Tank,
+                        b'#' | b' ' => Entity::Wall,
+                        b'D' => {
+                            pos = Some(Point::from((y, x)));
+                            Entity::Empty
+                        }
+                        b => panic!("Unexpected maze tile {b}"),
+                    })
+                    .collect()
+            })
+            .collect_vec()
+            .into();
+        Self { grid, pos: pos.expect("no drone in the maze") }
+    }
+}
+
+impl Environment for Maze {
+    fn try_move(&mut self, d: Direction) -> Entity {
+        let e = self.grid.get(self.pos + d).copied().unwrap_or(Entity::Wall);
+        if e != Entity::Wall {
+            self.pos = self.pos + d;
+        }
+        e
+    }
+}
+
+struct State<E> {
+    drone: E,
     entities: HashMap<Point, Entity>,
+    /// Relative to where the drone started.
     pos: Point,
+    render: bool,
 }
 
-impl<'a> State<'a> {
-    fn new(drone: Drone<'a>) -> Self {
-        Self { drone, entities: HashMap::from([(Point(0, 0), Entity::Empty)]), pos: Point(0, 0) }
+impl<E: Environment> State<E> {
+    fn new(drone: E) -> Self {
+        let entities = HashMap::from([(Point(0, 0), Entity::Empty)]);
+        Self { drone, entities, pos: Point(0, 0), render: false }
     }
 
     fn print(&self) {
+        if !self.render {
+            return;
+        }
         let (minx, maxx) = self.entities.keys().map(|p| p.0).minmax().into_option().unwrap();
         let (miny, maxy) = self.entities.keys().map(|p| p.1).minmax().into_option().unwrap();
         let offset = Direction::new(minx, miny);
@@ -60,21 +125,16 @@ impl<'a> State<'a> {
 
     fn slam(&mut self) {
         self.print();
-        for ((d, dv), (b, bv)) in [
-            ((1, Direction::UP), (2, Direction::DOWN)),
-            ((2, Direction::DOWN), (1, Direction::UP)),
-            ((3, Direction::LEFT), (4, Direction::RIGHT)),
-            ((4, Direction::RIGHT), (3, Direction::LEFT)),
-        ] {
+        for dv in [Direction::UP, Direction::DOWN, Direction::LEFT, Direction::RIGHT] {
             let np = self.pos + dv;
             if self.get(np).is_none() {
-                let e = self.drone.try_move(d);
+                let e = self.drone.try_move(dv);
                 self.put(np, e);
                 if matches!(e, Entity::Empty | Entity::Tank) {
                     self.pos = np;
                     self.slam();
-                    self.drone.try_move(b);
-                    self.pos = np + bv;
+                    self.drone.try_move(dv * -1);
+                    self.pos = np - dv;
                 }
             }
         }
@@ -101,11 +161,80 @@ impl<'a> State<'a> {
     }
 }
 
-fn main() {
-    let mut program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
-    let mut state = State::new(Drone(program.deferred_run()));
+fn solve(drone: impl Environment) {
+    let mut state = State::new(drone);
+    state.render = true;
     terminal::clear(stdout());
     state.slam();
     assert_eq!(state.pos, Point(0, 0));
     dbg!(state.bfs());
 }
+
+fn main() {
+    // `--maze <file>` explores a maze drawn in ASCII instead of running the drone program.
+    let args = env::args().skip(1).collect_vec();
+    if let [flag, path] = &args[..] {
+        assert_eq!(flag, "--maze", "usage: d15 [--maze <file>]");
+        return solve(Maze::parse(&read_to_string(path).unwrap()));
+    }
+    let mut program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
+    solve(Drone(program.deferred_run()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explore(maze: &str) -> (usize, usize) {
+        let maze = Maze::parse(maze);
+        let start = maze.pos;
+        let mut state = State::new(maze);
+        state.slam();
+        assert_eq!((state.pos, state.drone.pos), (Point(0, 0), start));
+        let open = state.drone.grid.iter().flatten().filter(|&&e| e != Entity::Wall).count();
+        let mapped = state.entities.values().filter(|&&e| e != Entity::Wall).count();
+        assert_eq!(mapped, open);
+        state.bfs()
+    }
+
+    #[test]
+    fn test_corridor() {
+        assert_eq!(explore("#####\n#D.O#\n#####"), (2, 2));
+    }
+
+    #[test]
+    fn test_loop() {
+        let maze = r"
+#####
+#D..#
+#.#.#
+#..O#
+#####";
+        assert_eq!(explore(maze.trim_start()), (4, 4));
+    }
+
+    #[test]
+    fn test_example() {
+        // The oxygen example from the puzzle, which takes 4 minutes to fill.
+        let maze = r"
+ ##
+#.D##
+#.#..#
+#.O.#
+ ###";
+        assert_eq!(explore(maze.trim_start_matches('\n')), (4, 4));
+    }
+
+    #[test]
+    fn test_loops() {
+        let maze = r"
+#########
+#D......#
+#.#.###.#
+#...#O..#
+###.#.#.#
+#.......#
+#########";
+        assert_eq!(explore(maze.trim_start()), (10, 10));
+    }
+}
@@ -1,7 +1,7 @@
 use itertools::{iproduct, Itertools};
 use std::collections::HashMap;
 use std::io::{stdin, stdout, BufRead};
-use utils::{terminal, BatchLines, Direction, Grid, Intcode, Point};
+use utils::{terminal, Ascii, Direction, Grid, Intcode, Point};
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 #[repr(u8)]
@@ -12,25 +12,135 @@ enum Entity {
 
 struct State {
     grid: Grid<Entity>,
+    /// Where the robot is and which way it faces.
+    robot: (Point, Direction),
+}
+
+/// Movement functions may be at most this long, and so may the main routine.
+const MAX_LEN: usize = 20;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Routines {
+    main: String,
+    functions: [String; 3],
+}
+
+/// Splits `moves` into calls to at most three functions, each made of whole moves, such that
+/// no routine is longer than [`MAX_LEN`]. There are no routines for a robot with nowhere to go.
+fn compress(moves: &[String]) -> Option<Routines> {
+    fn search<'a>(
+        moves: &'a [String],
+        calls: &mut Vec<usize>,
+        functions: &mut Vec<&'a [String]>,
+    ) -> bool {
+        if moves.is_empty() {
+            return true;
+        }
+        if 2 * calls.len() + 1 > MAX_LEN {
+            return false;
+        }
+        for f in 0..functions.len() {
+            if moves.starts_with(functions[f]) {
+                calls.push(f);
+                if search(&moves[functions[f].len()..], calls, functions) {
+                    return true;
+                }
+                calls.pop();
+            }
+        }
+        if functions.len() < 3 {
+            let fits = (1..=moves.len()).take_while(|&n| moves[..n].join(",").len() <= MAX_LEN);
+            for n in fits.collect_vec().into_iter().rev() {
+                functions.push(&moves[..n]);
+                calls.push(functions.len() - 1);
+                if search(&moves[n..], calls, functions) {
+                    return true;
+                }
+                calls.pop();
+                functions.pop();
+            }
+        }
+        false
+    }
+
+    let (mut calls, mut functions) = (vec![], vec![]);
+    if !search(moves, &mut calls, &mut functions) || functions.is_empty() {
+        return None;
+    }
+    let main = calls.iter().map(|&f| (b'A' + f as u8) as char).join(",");
+    // The robot asks for all three functions, so unused ones repeat the first.
+    let function = |f: usize| functions.get(f).unwrap_or(&functions[0]).join(",");
+    Some(Routines { main, functions: [function(0), function(1), function(2)] })
 }
 
 impl State {
     fn parse(reader: impl BufRead) -> Self {
+        let mut robot = None;
         let grid = reader
             .lines()
-            .map(|e| {
+            .enumerate()
+            .map(|(y, e)| {
                 e.unwrap()
                     .bytes()
-                    .map(|c| match c {
-                        b'.' => Entity::Space,
-                        b'#' | b'<' | b'>' | b'^' | b'v' => Entity::Scaffold,
-                        b => panic!("Unexpected input {b}"),
+                    .enumerate()
+                    .map(|(x, c)| {
+                        let facing = match c {
+                            b'.' => return Entity::Space,
+                            b'#' => return Entity::Scaffold,
+                            b'^' => Direction::UP,
+                            b'>' => Direction::RIGHT,
+                            b'v' => Direction::DOWN,
+                            b'<' => Direction::LEFT,
+                            b => panic!("Unexpected input {b}"),
+                        };
+                        robot = Some((Point::from((y, x)), facing));
+                        Entity::Scaffold
                     })
                     .collect()
             })
             .collect::<Vec<_>>()
             .into();
-        Self { grid }
+        Self { grid, robot: robot.expect("no robot") }
+    }
+
+    fn is_scaffold(&self, p: Point) -> bool {
+        self.grid.get(p) == Some(&Entity::Scaffold)
+    }
+
+    /// The moves that take the robot along the scaffold to its far end, like `R,10`, turning
+    /// only where it has to.
+    fn path(&self) -> Vec<String> {
+        let (mut p, mut dir) = self.robot;
+        let walk = |p: &mut Point, dir: Direction| {
+            let mut n = 0;
+            while self.is_scaffold(*p + dir) {
+                *p = *p + dir;
+                n += 1;
+            }
+            n
+        };
+        let mut moves = vec![];
+        // The robot may start out facing along the scaffold, or away from its only way out.
+        let sideways =
+            self.is_scaffold(p + dir.rotate()) || self.is_scaffold(p + dir.counter_rotate());
+        if self.is_scaffold(p + dir) {
+            moves.push(walk(&mut p, dir).to_string());
+        } else if !sideways && self.is_scaffold(p - dir) {
+            dir = dir * -1;
+            moves.push(format!("R,R,{}", walk(&mut p, dir)));
+        }
+        loop {
+            let turn = if self.is_scaffold(p + dir.rotate()) {
+                dir = dir.rotate();
+                "R"
+            } else if self.is_scaffold(p + dir.counter_rotate()) {
+                dir = dir.counter_rotate();
+                "L"
+            } else {
+                return moves;
+            };
+            moves.push(format!("{turn},{}", walk(&mut p, dir)));
+        }
     }
 
     fn part1(&self) -> usize {
@@ -48,29 +158,33 @@ impl State {
 
 fn main() {
     let mut program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
-    let output: String = program.clone().run(std::iter::empty()).map(|s| s as u8 as char).collect();
+    let output = program.clone().ascii_run().lines().join("\n");
     let state = State::parse(output.trim_ascii().as_bytes());
     dbg!(state.part1());
 
+    let moves = state.path();
+    println!("Path: {}", moves.join(","));
+    let routines = compress(&moves).expect("the path doesn't fit in three functions");
     let prompt_response = HashMap::from([
-        ("Main:", "A,B,A,C,B,C,A,B,A,C\n"),
-        ("Function A:", "R,10,L,8,R,10,R,4\n"),
-        ("Function B:", "L,6,L,6,R,10\n"),
-        ("Function C:", "L,6,R,12,R,12,R,10\n"),
-        ("Continuous video feed?", "y\n"),
+        ("Main:", routines.main.as_str()),
+        ("Function A:", &routines.functions[0]),
+        ("Function B:", &routines.functions[1]),
+        ("Function C:", &routines.functions[2]),
+        ("Continuous video feed?", "y"),
     ]);
     program.code[0] = 2;
-    let deferred = program.deferred_run();
+    let machine = program.ascii_run();
 
     let mut first_frame = true;
     let mut new_frame = false;
-    let mut output = deferred.iter().peekable();
-    let lines = output
-        .by_ref()
-        .peeking_take_while(|&i| i < 256 && (i as u8).is_ascii())
-        .map(|a| a as u8 as char)
-        .batch_lines();
-    for line in lines {
+    for event in machine.events() {
+        let line = match event {
+            Ascii::Line(line) => line,
+            Ascii::Value(answer) => {
+                println!("Part2 answer: {answer}");
+                break;
+            }
+        };
         let resp = prompt_response.get(line.trim_ascii());
         if !first_frame && new_frame && resp.is_none() {
             std::thread::sleep(std::time::Duration::from_millis(5));
@@ -78,14 +192,13 @@ fn main() {
         }
         println!("{line}");
         if let Some(resp) = resp {
-            deferred.send_seq(resp.bytes().map(|b| b.into()));
-            println!("{resp}");
+            machine.send_line(resp);
+            println!("{resp}\n");
             terminal::clear(stdout());
             first_frame = false;
         }
         new_frame = line.is_empty();
     }
-    println!("Part2 answer: {}", output.next().unwrap());
 }
 
 #[cfg(test)]
@@ -106,4 +219,55 @@ mod tests {
         .trim_ascii();
         assert_eq!(State::parse(input.as_bytes()).part1(), 76);
     }
+
+    #[test]
+    fn test_part2() {
+        let input = r"
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......
+"
+        .trim_ascii();
+        let moves = State::parse(input.as_bytes()).path();
+        assert_eq!(moves.join(","), "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2");
+        let routines = compress(&moves).unwrap();
+        let expanded = routines
+            .main
+            .split(',')
+            .map(|f| &routines.functions[(f.as_bytes()[0] - b'A') as usize])
+            .join(",");
+        assert_eq!(expanded, moves.join(","));
+        assert!(routines.functions.iter().all(|f| f.len() <= MAX_LEN));
+        assert!(routines.main.len() <= MAX_LEN);
+
+        // Without any repetition, three functions can't cover 13 moves.
+        assert_eq!(compress(&(10..23).map(|n| format!("R,{n}")).collect_vec()), None);
+    }
+
+    #[test]
+    fn test_start_orientation() {
+        let facing_along = State::parse(">###\n...#\n...#".as_bytes());
+        assert_eq!(facing_along.path().join(","), "3,R,2");
+        let facing_away = State::parse("<###\n...#".as_bytes());
+        assert_eq!(facing_away.path().join(","), "R,R,3,R,1");
+        let routines = compress(&facing_away.path()).unwrap();
+        assert_eq!(routines.main, "A");
+        assert_eq!(routines.functions[0], "R,R,3,R,1");
+
+        let stuck = State::parse("..\n^.".as_bytes());
+        assert!(stuck.path().is_empty());
+        assert_eq!(compress(&stuck.path()), None);
+    }
 }
@@ -1,15 +1,73 @@
 use itertools::iproduct;
+use std::collections::HashMap;
+use std::env;
 use std::io::stdin;
 use std::ops::Range;
 use utils::{Grid, Intcode};
 
-fn plot(program: &Intcode, xs: Range<usize>, ys: Range<usize>) {
-    let mut grid = Grid::new(' ', ys.end - ys.start, xs.end - xs.start);
-    for (x, y) in iproduct!(xs.clone(), ys.clone()) {
-        grid[(y - ys.start, x - xs.start)] = if is_pulled(program, x, y) { '#' } else { '.' };
+/// Follows the edges of the tractor beam row by row. Probes are memoized, and each row starts
+/// searching from the edges of the one above, since the beam only drifts right.
+struct Beam<F> {
+    probe: F,
+    pulled: HashMap<(usize, usize), bool>,
+    /// The pulled columns of each row tracked so far. Rows close to the emitter may be empty.
+    rows: Vec<Range<usize>>,
+}
+
+impl<F: FnMut(usize, usize) -> bool> Beam<F> {
+    fn new(probe: F) -> Self {
+        Self { probe, pulled: HashMap::new(), rows: vec![] }
+    }
+
+    fn is_pulled(&mut self, x: usize, y: usize) -> bool {
+        *self.pulled.entry((x, y)).or_insert_with(|| (self.probe)(x, y))
+    }
+
+    fn row(&mut self, y: usize) -> Range<usize> {
+        while self.rows.len() <= y {
+            let y = self.rows.len();
+            let prev = self.rows.iter().rev().find(|r| !r.is_empty()).cloned().unwrap_or(0..0);
+            // Past the rows near the emitter, the left edge is at most a step or two away.
+            let limit = prev.end + 10 * (y + 1);
+            let row = match (prev.start..limit).find(|&x| self.is_pulled(x, y)) {
+                Some(lo) => {
+                    let mut hi = prev.end.max(lo + 1);
+                    while self.is_pulled(hi, y) {
+                        hi += 1;
+                    }
+                    lo..hi
+                }
+                None => 0..0,
+            };
+            self.rows.push(row);
+        }
+        self.rows[y].clone()
+    }
+
+    /// The top left corner of the first `n` by `n` square that fits in the beam, closest to the
+    /// emitter. An empty square fits right at the emitter.
+    fn square(&mut self, n: usize) -> (usize, usize) {
+        if n == 0 {
+            return (0, 0);
+        }
+        for bottom in n - 1.. {
+            let x = self.row(bottom).start;
+            let top = self.row(bottom + 1 - n);
+            if !self.rows[bottom].is_empty() && top.start <= x && x + n <= top.end {
+                return (x, bottom + 1 - n);
+            }
+        }
+        unreachable!()
+    }
+
+    fn plot(&mut self, xs: Range<usize>, ys: Range<usize>) -> Grid<char> {
+        let mut grid = Grid::new(' ', ys.len(), xs.len());
+        for (x, y) in iproduct!(xs.clone(), ys.clone()) {
+            let c = if self.is_pulled(x, y) { '#' } else { '.' };
+            grid[(y - ys.start, x - xs.start)] = c;
+        }
+        grid
     }
-    println!("Plot ({xs:?}) x ({ys:?}):");
-    println!("{grid}");
 }
 
 fn is_pulled(program: &Intcode, x: usize, y: usize) -> bool {
@@ -20,25 +78,68 @@ fn is_pulled(program: &Intcode, x: usize, y: usize) -> bool {
     }
 }
 
-fn part1(program: &Intcode) -> usize {
-    iproduct!(0..50, 0..50).filter(|&(x, y)| is_pulled(program, x, y)).count()
+fn part1(beam: &mut Beam<impl FnMut(usize, usize) -> bool>) -> usize {
+    iproduct!(0..50, 0..50).filter(|&(x, y)| beam.is_pulled(x, y)).count()
 }
 
-fn part2(program: &Intcode, x: usize, y: usize) -> usize {
-    // plot(program, x..x + 10, y..y + 10);
-    let test_x = (0..100).all(|d| is_pulled(program, x + d, y));
-    let test_y = (0..100).all(|d| is_pulled(program, x, y + d));
-    if test_x && test_y {
-        return dbg!(x * 10000 + y);
-    }
-    let move_x = !test_y && is_pulled(program, x + 1, y);
-    let move_y = !test_x && is_pulled(program, x, y + 1);
-    assert!(move_x || move_y);
-    part2(program, x + move_x as usize, y + move_y as usize)
+fn main() {
+    // `--plot` draws the beam around the square, with the square marked.
+    let plot = env::args().any(|a| a == "--plot");
+    let mut program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
+    program.predecode();
+    let mut beam = Beam::new(|x, y| is_pulled(&program, x, y));
+    let (x, y) = beam.square(100);
+    if plot {
+        let (xs, ys) = (x.saturating_sub(5)..x + 105, y.saturating_sub(5)..y + 105);
+        let mut grid = beam.plot(xs.clone(), ys.clone());
+        for (dx, dy) in iproduct!(0..100, 0..100) {
+            grid[(y + dy - ys.start, x + dx - xs.start)] = 'O';
+        }
+        println!("Plot ({xs:?}) x ({ys:?}):");
+        println!("{grid}");
+    }
+    dbg!(part1(&mut beam), x * 10000 + y);
 }
 
-fn main() {
-    let program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
-    plot(&program, 120..140, 100..120);
-    dbg!(part1(&program), part2(&program, 120, 100));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A cone between the lines x = 5y/4 and x = 3y/2, gappy near the emitter like the real one.
+    fn cone(x: usize, y: usize) -> bool {
+        5 * y <= 4 * x && 2 * x <= 3 * y
+    }
+
+    fn brute_force(n: usize) -> (usize, usize) {
+        iproduct!(0..300, 0..300)
+            .map(|(y, x)| (x, y))
+            .find(|&(x, y)| iproduct!(x..x + n, y..y + n).all(|(x, y)| cone(x, y)))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_square() {
+        let mut probes = 0;
+        let mut beam = Beam::new(|x, y| {
+            probes += 1;
+            cone(x, y)
+        });
+        assert_eq!(beam.row(0), 0..1);
+        assert_eq!(beam.row(1), 0..0);
+        assert_eq!(beam.row(9), 12..14);
+        assert_eq!(beam.square(0), (0, 0));
+        for n in [1, 2, 3, 5, 10, 20] {
+            let (x, y) = beam.square(n);
+            assert_eq!((x, y), brute_force(n), "square of size {n}");
+        }
+        drop(beam);
+        assert!(probes < 2000, "{probes} probes");
+    }
+
+    #[test]
+    fn test_plot() {
+        let mut beam = Beam::new(cone);
+        let grid = beam.plot(0..6, 0..4);
+        assert_eq!(grid.to_string(), "#.....\n......\n...#..\n....#.\n");
+    }
 }
@@ -1,47 +1,73 @@
 use std::io::stdin;
-use utils::Intcode;
+use utils::springscript::{Mode, Script};
+use utils::{Ascii, Intcode};
 
-fn feed_input(program: &Intcode, input: &str) {
-    for n in program.clone().run(input.bytes().map(|b| b.into())) {
-        if n < 256 && (n as u8).is_ascii() {
-            print!("{}", n as u8 as char);
-        } else {
-            println!("Answer {n}");
-            break;
+// Jump if there is a hole ahead and ground to land on.
+const WALK: &str = "!A | !C & D";
+// ABCDEFGHI
+//    *   *
+//    **   *
+//    ***
+const RUN: &str = "(!A | !B | !C) & D & (H | E & (F | I))";
+
+// Hulls the droid fell into while these scripts were worked out.
+const WALK_HULLS: &[&str] = &["#####.###########", "#####...#########", "#####..#.########"];
+const RUN_HULLS: &[&str] = &[
+    "#####.###########",
+    "#####...#########",
+    "#####..#.########",
+    "#####.#.##..#.###",
+    "#####.##.##.#.###",
+    "#####...##.#.####",
+    "#####.#..########",
+    "#####..###.#..###",
+];
+
+fn compile(expr: &str, mode: Mode, hulls: &[&str]) -> Script {
+    let script = Script::compile(expr, mode).unwrap();
+    for hull in hulls {
+        if let Err(x) = script.simulate(hull) {
+            panic!("`{expr}` falls into {hull} at {x}");
+        }
+    }
+    script
+}
+
+fn feed_input(program: &Intcode, script: &Script) {
+    let mut program = program.clone();
+    let machine = program.ascii_run();
+    for line in machine.read_until_prompt("Input instructions:").unwrap() {
+        println!("{line}");
+    }
+    machine.send(&script.to_string());
+    for event in machine.events() {
+        match event {
+            Ascii::Line(line) => println!("{line}"),
+            Ascii::Value(n) => {
+                println!("Answer {n}");
+                break;
+            }
         }
     }
 }
 
 fn main() {
     let program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
+    feed_input(&program, &compile(WALK, Mode::Walk, WALK_HULLS));
+    feed_input(&program, &compile(RUN, Mode::Run, RUN_HULLS));
+}
 
-    let part1_input = "
-NOT C J
-AND D J
-NOT A T
-OR T J
-WALK
-".trim_start();
-    feed_input(&program, part1_input);
-    // ABCDEFGHI
-    //    *   *
-    //    **   *
-    //    ***
-    let part2_input = "
-NOT A T
-OR T J
-NOT B T
-OR T J
-NOT C T
-OR T J
-AND D J
-NOT D T
-OR F T
-OR I T
-AND E T
-OR H T
-AND T J
-RUN
-".trim_start();
-    feed_input(&program, part2_input);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts() {
+        let walk = compile(WALK, Mode::Walk, WALK_HULLS);
+        assert_eq!(walk.to_string(), "NOT A J\nNOT C T\nAND D T\nOR T J\nWALK\n");
+        let run = compile(RUN, Mode::Run, RUN_HULLS);
+        assert!(run.instructions.len() <= 15);
+        // Walking doesn't see far enough for these.
+        assert!(RUN_HULLS.iter().any(|hull| walk.simulate(hull).is_err()));
+    }
 }
@@ -1,76 +1,111 @@
-use itertools::{chain, Itertools};
 use std::io::stdin;
-use std::sync::{mpsc, Condvar, Mutex};
-use utils::Intcode;
+use utils::{Intcode, Scheduler, Status};
 
-type Packet = [isize; 2];
-
-fn main() {
-    let program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
-    let (txs, rxs): (Vec<_>, Vec<_>) =
-        std::iter::repeat_with(mpsc::channel::<Packet>).take(50).unzip();
-    let nat_packet = Mutex::new([-1, -1]);
-    let (condvar, idle_count) = (Condvar::new(), Mutex::new(0));
-    std::thread::scope(|s| {
-        let txs = &txs;
-        let nat_packet = &nat_packet;
-        let (condvar, idle_count) = (&condvar, &idle_count);
-        s.spawn(move || {
-            let mut last_y = -1;
-            loop {
-                let idle = condvar.wait(idle_count.lock().unwrap()).unwrap();
-                let [x, y] = *nat_packet.lock().unwrap();
-                if *idle < 50 || (x, y) == (-1, -1) {
-                    continue;
-                }
-                println!("NAT x={x} y={y}");
-                if last_y == y {
-                    std::process::exit(0);
+/// Runs the network until the NAT delivers the same y twice in a row. Returns the y of the first
+/// packet sent to the NAT and that repeated y.
+fn solve(program: &Intcode, size: usize) -> (isize, isize) {
+    let mut scheduler = Scheduler::new(vec![program.clone(); size]);
+    scheduler.idle_input = Some(-1);
+    for i in 0..size {
+        scheduler.send(i, [i as isize]);
+    }
+    let mut buffers = vec![vec![]; size];
+    let mut nat = None;
+    let mut first_y = None;
+    let mut last_y = None;
+    loop {
+        let status = scheduler.run(|router, id, value| {
+            buffers[id].push(value);
+            if let &[a, x, y] = &buffers[id][..] {
+                buffers[id].clear();
+                match a {
+                    255 => {
+                        first_y.get_or_insert(y);
+                        nat = Some([x, y]);
+                    }
+                    0.. if (a as usize) < router.count() => router.send(a as usize, [x, y]),
+                    _ => { /* ignored */ }
                 }
-                last_y = y;
-                txs[0].send([x, y]).unwrap();
-                let _idle = condvar.wait_while(idle, |idle| *idle == 50).unwrap();
             }
         });
-        for (i, rx) in rxs.into_iter().enumerate() {
-            let mut program = program.clone();
-            s.spawn(move || {
-                let mut idle = 0u32;
-                let output = program.run(chain![
-                    std::iter::once(i as isize),
-                    std::iter::repeat_with(|| {
-                        // Throttle
-                        std::thread::sleep(std::time::Duration::from_millis(1));
-                        if let Ok([x, y]) = rx.try_recv() {
-                            if idle >= 20 {
-                                *idle_count.lock().unwrap() -= 1;
-                                condvar.notify_one();
-                            }
-                            idle = 0;
-                            vec![x, y].into_iter()
-                        } else {
-                            idle = idle.saturating_add(1);
-                            if idle == 20 {
-                                *idle_count.lock().unwrap() += 1;
-                                condvar.notify_one();
-                            }
-                            vec![-1].into_iter()
-                        }
-                    })
-                    .flatten(),
-                ]);
-                for (a, x, y) in output.tuple_windows() {
-                    // println!("i={i} a={a}, x={x}, y={y}");
-                    match a {
-                        0..50 => txs[a as usize].send([x, y]).unwrap(),
-                        255 => {
-                            *nat_packet.lock().unwrap() = [x, y];
-                            condvar.notify_one();
-                        }
-                        _ => { /* ignored */ }
-                    }
-                }
-            });
+        assert_eq!(status, Ok(Status::Idle));
+        let [x, y] = nat.expect("network is idle before any packet reached the NAT");
+        if last_y == Some(y) {
+            return (first_y.unwrap(), y);
+        }
+        last_y = Some(y);
+        scheduler.send(0, [x, y]);
+    }
+}
+
+fn main() {
+    let program: Intcode = stdin().lines().next().unwrap().unwrap().parse().unwrap();
+    dbg!(solve(&program, 50));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tiny NIC: after learning its address, forwards (address + 1, x, y + 1) for each received
+    // packet; the last machine reports to the NAT instead.
+    const NIC: &str = r"
+        in [addr]
+        add [addr], 1, [next]
+        eq [next], 3, [t]
+        jif [t], loop
+        add 255, 0, [next]
+    loop:
+        in [x]
+        eq [x], -1, [t]
+        jit [t], loop
+        in [y]
+        add [y], 1, [y]
+        out [next]
+        out [x]
+        out [y]
+        jit 1, loop
+    addr: .data 0
+    next: .data 0
+    x:    .data 0
+    y:    .data 0
+    t:    .data 0
+    ";
+
+    #[test]
+    fn test_nat() {
+        // Nothing is sent until the NAT wakes machine 0 up, so the NAT never gets a packet.
+        let nic = Intcode::assemble(NIC).unwrap();
+        let mut scheduler = Scheduler::new(vec![nic.clone(); 3]);
+        scheduler.idle_input = Some(-1);
+        for i in 0..3 {
+            scheduler.send(i, [i as isize]);
         }
-    });
+        let mut outputs = vec![];
+        assert_eq!(scheduler.run(|_, id, x| outputs.push((id, x))), Ok(Status::Idle));
+        assert!(outputs.is_empty());
+
+        scheduler.send(0, [5, 10]);
+        assert_eq!(scheduler.run(|_, id, x| outputs.push((id, x))), Ok(Status::Idle));
+        assert_eq!(outputs, [(0, 1), (0, 5), (0, 11)]);
+    }
+
+    #[test]
+    fn test_solve() {
+        // Machine 0 sends one packet to the NAT, then every machine just polls.
+        const SEED: &str = r"
+            in [addr]
+            jit [addr], nic
+            out 255
+            out 0
+            out 7
+        nic:
+            in [x]
+            jit 1, nic
+        addr: .data 0
+        x:    .data 0
+        ";
+        // The NAT keeps delivering (0, 7) to machine 0 which ignores it, so y repeats at once.
+        assert_eq!(solve(&Intcode::assemble(SEED).unwrap(), 3), (7, 7));
+    }
 }
@@ -1,84 +1,387 @@
 use rustyline::{error::ReadlineError, DefaultEditor};
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::env;
-use std::fs::read_to_string;
-use std::sync::mpsc;
-use utils::{BatchLines, Intcode};
+use std::fs::{read_to_string, File};
+use utils::{AsciiMachine, ErrorKind, Event, Intcode};
 
-fn main() {
-    let argv1 = env::args().nth(1).expect("argv[1] must be the intcode program");
-    let mut program: Intcode = read_to_string(argv1).unwrap().parse().unwrap();
-    let (tx, rx) = mpsc::channel();
-
-    let input = r"
-south
-south
-take tambourine
-north
-north
-west
-south
-take polygon
-north
-east
-north
-west
-take boulder
-east
-north
-take manifold
-north
-take hologram
-south
-west
-take fuel cell
-south
-east
-south
-take fixed point
-north
-west
-north
-north
-take wreath
-east
-east
-drop tambourine
-drop fuel cell
-drop hologram
-drop wreath
-inv
-north
-";
-    /*
-    drop polygon
-    drop manifold
-    drop boulder
-    drop fixed point
-    */
-    for c in input.bytes() {
-        tx.send(c.into()).unwrap();
-    }
-    std::thread::spawn(move || {
-        let mut rl = DefaultEditor::new().unwrap();
+/// More than any command needs. Only the infinite loop item uses it up.
+const STEP_BUDGET: usize = 10_000_000;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Reply {
+    /// The text printed before the next `Command?` prompt.
+    Prompt(String),
+    /// The game is over, either won or lost.
+    Halted(String),
+    Looping,
+}
+
+#[derive(Clone)]
+struct Droid {
+    program: Intcode,
+}
+
+impl Droid {
+    fn new(program: Intcode) -> (Self, Reply) {
+        let mut droid = Self { program };
+        let reply = droid.read();
+        (droid, reply)
+    }
+
+    fn read(&mut self) -> Reply {
+        self.program.set_step_budget(Some(STEP_BUDGET));
+        let mut text = String::new();
         loop {
-            match rl.readline("> ") {
-                Ok(line) => {
-                    for c in line.bytes() {
-                        tx.send(c.into()).unwrap();
-                    }
-                    tx.send(b'\n'.into()).unwrap();
+            match self.program.resume() {
+                Ok(Event::Output(x)) => text.push(u8::try_from(x).map_or('?', char::from)),
+                Ok(Event::NeedsInput) => {
+                    let text = text.trim_end().trim_end_matches("Command?");
+                    return Reply::Prompt(text.to_string());
                 }
+                Ok(_) => return Reply::Halted(text),
+                Err(e) if e.kind == ErrorKind::BudgetExhausted => return Reply::Looping,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Reply {
+        self.program.extend_input(command.bytes().chain([b'\n']).map(isize::from));
+        self.read()
+    }
+
+    /// Moves through `door`, and returns the rooms seen on the way, or the final text if that
+    /// ended the game.
+    fn go(&mut self, door: &str) -> Result<Vec<Room>, String> {
+        match self.send(door) {
+            Reply::Prompt(text) => Ok(parse_rooms(&text)),
+            Reply::Halted(text) => Err(text),
+            reply @ Reply::Looping => panic!("lost going {door}: {reply:?}"),
+        }
+    }
+
+    /// Whether the droid can still move after taking `item`, trying the move through `door`
+    /// on a copy of the machine. Ending the game by the move, which only the pressure-sensitive
+    /// floor does, counts as moving.
+    fn is_safe(&self, item: &str, door: &str) -> bool {
+        let mut droid = self.clone();
+        matches!(droid.send(&format!("take {item}")), Reply::Prompt(_))
+            && match droid.send(door) {
+                Reply::Prompt(text) => !parse_rooms(&text).is_empty(),
+                Reply::Halted(_) => true,
+                Reply::Looping => false,
+            }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct Room {
+    name: String,
+    doors: Vec<String>,
+    items: Vec<String>,
+}
+
+/// Parses every room description in `text`. More than one means the droid was moved on, as
+/// the pressure-sensitive floor does with droids of the wrong weight.
+fn parse_rooms(text: &str) -> Vec<Room> {
+    let mut rooms: Vec<Room> = vec![];
+    let mut list = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("== ").and_then(|l| l.strip_suffix(" ==")) {
+            rooms.push(Room { name: name.to_string(), ..Default::default() });
+            list = None;
+        } else if line == "Doors here lead:" || line == "Items here:" {
+            list = Some(line.starts_with("Doors"));
+        } else if let (Some(doors), Some(room), Some(entry)) =
+            (list, rooms.last_mut(), line.strip_prefix("- "))
+        {
+            let list = if doors { &mut room.doors } else { &mut room.items };
+            list.push(entry.to_string());
+        } else {
+            list = None;
+        }
+    }
+    rooms
+}
+
+fn opposite(door: &str) -> &str {
+    match door {
+        "north" => "south",
+        "south" => "north",
+        "east" => "west",
+        "west" => "east",
+        _ => panic!("unknown door {door}"),
+    }
+}
+
+/// The route from the start to the security checkpoint, and the door to the floor from there.
+#[derive(Debug, Clone, Default)]
+struct Checkpoint {
+    route: Vec<String>,
+    door: String,
+}
+
+struct Explorer {
+    droid: Droid,
+    visited: HashSet<String>,
+    route: Vec<String>,
+    items: Vec<String>,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl Explorer {
+    /// Visits every room reachable from `room` by DFS, taking all items that are safe to carry,
+    /// and comes back. Stops with the final text if the items picked up so far already get the
+    /// droid past the floor.
+    fn explore(&mut self, room: &Room) -> Result<(), String> {
+        self.visited.insert(room.name.clone());
+        for item in &room.items {
+            if self.droid.is_safe(item, &room.doors[0]) {
+                assert!(matches!(self.droid.send(&format!("take {item}")), Reply::Prompt(_)));
+                self.items.push(item.clone());
+            }
+        }
+        for door in &room.doors {
+            let rooms = self.droid.go(door)?;
+            let next = rooms.last().expect("no room after moving");
+            if next.name == room.name {
+                // Turned back by the pressure-sensitive floor.
+                let route = self.route.clone();
+                self.checkpoint = Some(Checkpoint { route, door: door.clone() });
+                continue;
+            }
+            if !self.visited.contains(&next.name) {
+                self.route.push(door.clone());
+                self.explore(next)?;
+                self.route.pop();
+            }
+            self.droid.go(opposite(door))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tries item subsets in Gray code order on the checkpoint, so that each attempt takes or drops
+/// a single item. Returns the final message of the game.
+fn crack(droid: &mut Droid, items: &[String], door: &str) -> String {
+    // Bit i set means item i is dropped. All items are held to begin with.
+    let mut dropped = 0usize;
+    for i in 0..1usize << items.len() {
+        let gray = i ^ (i >> 1);
+        if let Some(bit) = (0..items.len()).find(|&b| (gray ^ dropped) >> b & 1 == 1) {
+            let verb = if gray >> bit & 1 == 1 { "drop" } else { "take" };
+            assert!(matches!(droid.send(&format!("{verb} {}", items[bit])), Reply::Prompt(_)));
+        }
+        dropped = gray;
+        match droid.send(door) {
+            Reply::Halted(text) => return text,
+            Reply::Prompt(_) => {}
+            Reply::Looping => panic!("the floor went into a loop"),
+        }
+    }
+    panic!("no item subset gets past the floor");
+}
+
+fn password(text: &str) -> Option<&str> {
+    let (_, rest) = text.split_once("typing ")?;
+    rest.split_whitespace().next()
+}
+
+fn solve(program: &Intcode) -> String {
+    let (droid, reply) = Droid::new(program.clone());
+    let Reply::Prompt(text) = reply else { panic!("the game didn't start: {reply:?}") };
+    let start = parse_rooms(&text).pop().expect("no starting room");
+    let mut explorer =
+        Explorer { droid, visited: HashSet::new(), route: vec![], items: vec![], checkpoint: None };
+    let text = match explorer.explore(&start) {
+        Err(text) => text,
+        Ok(()) => {
+            let Explorer { mut droid, items, checkpoint, .. } = explorer;
+            let checkpoint = checkpoint.expect("no security checkpoint");
+            println!("Carrying {}", items.join(", "));
+            for door in &checkpoint.route {
+                droid.go(door).expect("the game ended before the checkpoint");
+            }
+            crack(&mut droid, &items, &checkpoint.door)
+        }
+    };
+    println!("{}", text.trim());
+    password(&text).expect("no password").to_string()
+}
+
+/// Plays interactively, recording the session to `record` if given.
+fn play(program: &mut Intcode, record: Option<String>) {
+    let transcript = RefCell::default();
+    let machine = AsciiMachine::from(program.deferred_run_recorded(&transcript));
+    let mut rl = DefaultEditor::new().unwrap();
+    loop {
+        let lines = machine.read_until_prompt("Command?");
+        for line in lines.as_ref().unwrap_or_else(|lines| lines) {
+            println!("{line}");
+        }
+        if lines.is_err() {
+            break;
+        }
+        println!("Command?");
+        let command = loop {
+            match rl.readline("> ") {
+                Ok(line) => break Some(line),
                 Err(ReadlineError::Interrupted) => {}
                 e => {
                     println!("Error {e:?}");
-                    break;
+                    break None;
                 }
             }
-        }
-    });
+        };
+        let Some(command) = command else { break };
+        machine.send_line(&command);
+    }
+    drop(machine);
+    if let Some(path) = record {
+        transcript.into_inner().save(File::create(path).unwrap()).unwrap();
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let argv1 = args.next().expect("argv[1] must be the intcode program");
+    let mut program: Intcode = read_to_string(argv1).unwrap().trim().parse().unwrap();
+    // `--play [transcript]` plays by hand, optionally recording the session for replay tests.
+    if args.next().as_deref() == Some("--play") {
+        play(&mut program, args.next());
+    } else {
+        println!("Password: {}", solve(&program));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EJECTED: &str = r#"
+
+== Pressure-Sensitive Floor ==
+Analyzing...
+
+Doors here lead:
+- south
+
+A loud, robotic voice says "Alert! Droids on this ship are heavier than the detected value!" and you are ejected back to the checkpoint.
+
+
+
+== Security Checkpoint ==
+In the next room, a pressure-sensitive floor will verify your identity.
+
+Doors here lead:
+- north
+- south
+
+Items here:
+- mutex
+"#;
+
+    #[test]
+    fn test_parse_rooms() {
+        let rooms = parse_rooms(EJECTED);
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(
+            rooms[0],
+            Room {
+                name: "Pressure-Sensitive Floor".to_string(),
+                doors: vec!["south".to_string()],
+                items: vec![]
+            }
+        );
+        assert_eq!(rooms[1].name, "Security Checkpoint");
+        assert_eq!(rooms[1].doors, ["north", "south"]);
+        assert_eq!(rooms[1].items, ["mutex"]);
+        assert!(parse_rooms("You can't move!!").is_empty());
+    }
+
+    #[test]
+    fn test_password() {
+        let text = "\"Oh, hello! You should be able to get in by typing 2228740 on the keypad.\"";
+        assert_eq!(password(text), Some("2228740"));
+    }
+
+    #[test]
+    fn test_droid() {
+        // Prints a prompt, takes one command and says goodbye. Saying "loop" makes it spin.
+        let program = Intcode::assemble(
+            r"
+                out 67
+                out 111
+                out 109
+                out 109
+                out 97
+                out 110
+                out 100
+                out 63
+                out 10
+                in [c]
+                eq [c], 108, [t]
+            spin:
+                jit [t], spin
+                out 98
+                out 121
+                out 101
+                out 10
+                hlt
+            c:  .data 0
+            t:  .data 0
+            ",
+        )
+        .unwrap();
+        let (mut droid, reply) = Droid::new(program);
+        assert_eq!(reply, Reply::Prompt(String::new()));
+        let mut copy = droid.clone();
+        assert_eq!(copy.send("loop"), Reply::Looping);
+        assert_eq!(droid.send("north"), Reply::Halted("bye\n".to_string()));
+    }
+
+    fn says(text: &str) -> String {
+        text.bytes().map(|b| format!("out {b}\n")).collect()
+    }
 
-    let output = program.run(rx);
-    for line in output.map(|b| b as u8 as char).batch_lines() {
-        println!("{line}");
+    #[test]
+    fn test_solved_while_exploring() {
+        // A checkpoint with a key, which is all it takes to get past the floor to the north.
+        let room =
+            "\n\n== Checkpoint ==\n\nDoors here lead:\n- north\n\nItems here:\n- key\n\nCommand?\n";
+        let ejected = "\n\n== Pressure-Sensitive Floor ==\n\nDoors here lead:\n- south\n\nAlert!";
+        let src = format!(
+            r"
+                {room}
+            read:
+                in [c]
+            skip:
+                in [t]
+                eq [t], 10, [t]
+                jif [t], skip
+                eq [c], 116, [t]
+                jit [t], take
+                jit [held], win
+                {ejected}
+                {room}
+                jif 0, read
+            take:
+                add 1, 0, [held]
+                {taken}
+                jif 0, read
+            win:
+                {won}
+                hlt
+            c:  .data 0
+            t:  .data 0
+            held: .data 0
+            ",
+            room = says(room),
+            ejected = says(ejected),
+            taken = says("\nYou take the key.\n\nCommand?\n"),
+            won = says("\nYou may proceed by typing 42 on the keypad.\n"),
+        );
+        assert_eq!(solve(&Intcode::assemble(&src).unwrap()), "42");
     }
 }
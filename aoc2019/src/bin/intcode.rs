@@ -0,0 +1,282 @@
+use itertools::Itertools;
+use std::env;
+use std::fs::File;
+use std::io::{self, stderr, stdin, stdout, BufRead, BufReader, Write};
+use utils::{ErrorKind, Event, Intcode, IntcodeError, TraceWriter, Tracer};
+
+const USAGE: &str = "\
+Usage: intcode <command> [options] <program>
+
+Commands:
+  run        run the program
+  trace      run the program, writing each executed instruction to stderr
+  patch      print the program after applying patches
+  disasm     print a disassembly
+  dot        print the control-flow graph in DOT format
+  decompile  print the program as pseudo-C
+  transpile  print the program as Rust source
+
+Options:
+  -i, --input <n,n,...>       queue numbers as input
+  -a, --ascii <line>          queue a line of ASCII text as input
+  -f, --input-file <file>     read input from a file, `-` for stdin
+      --input-format <fmt>    how to read input files: raw (numbers) or ascii
+      --format <fmt>          how to write output: raw (one number per line) or ascii
+  -p, --patch <addr>=<value>  write value to memory before starting
+      --steps <n>             stop after executing n instructions
+      --detect-loops          stop when the program loops without doing I/O
+
+Without any input options, input is read from stdin. The program is a file of comma separated
+numbers, `-` for stdin.";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Input {
+    Values(Vec<isize>),
+    Text(String),
+    File(String),
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct Options {
+    command: String,
+    program: String,
+    input: Vec<Input>,
+    ascii_input: bool,
+    ascii_output: bool,
+    patches: Vec<(usize, isize)>,
+    steps: Option<usize>,
+    detect_loops: bool,
+}
+
+fn parse_format(s: &str) -> Result<bool, String> {
+    match s {
+        "raw" => Ok(false),
+        "ascii" => Ok(true),
+        _ => Err(format!("unknown format `{s}`, expected raw or ascii")),
+    }
+}
+
+fn parse_values(s: &str) -> Result<Vec<isize>, String> {
+    s.split([',', ' ', '\t'])
+        .filter(|e| !e.is_empty())
+        .map(|e| e.parse().map_err(|_| format!("bad number `{e}`")))
+        .collect()
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut args = args.into_iter();
+    let mut options = Options::default();
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for `{arg}`"));
+        match arg.as_str() {
+            "-i" | "--input" => options.input.push(Input::Values(parse_values(&value()?)?)),
+            "-a" | "--ascii" => options.input.push(Input::Text(value()?)),
+            "-f" | "--input-file" => options.input.push(Input::File(value()?)),
+            "--input-format" => options.ascii_input = parse_format(&value()?)?,
+            "--format" => options.ascii_output = parse_format(&value()?)?,
+            "-p" | "--patch" => {
+                let patch = value()?;
+                let bad = || format!("bad patch `{patch}`, expected <addr>=<value>");
+                let (addr, v) = patch.split_once('=').ok_or_else(bad)?;
+                let addr = addr.trim().parse().map_err(|_| bad())?;
+                options.patches.push((addr, v.trim().parse().map_err(|_| bad())?));
+            }
+            "--steps" => {
+                let n = value()?;
+                options.steps = Some(n.parse().map_err(|_| format!("bad step count `{n}`"))?);
+            }
+            "--detect-loops" => options.detect_loops = true,
+            "-" => positional.push(arg),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+            _ => positional.push(arg),
+        }
+    }
+    let [command, program] = <[String; 2]>::try_from(positional)
+        .map_err(|_| "expected a command and a program".to_string())?;
+    (options.command, options.program) = (command, program);
+    Ok(options)
+}
+
+fn open(path: &str) -> io::Result<Box<dyn BufRead>> {
+    Ok(match path {
+        "-" => Box::new(stdin().lock()),
+        _ => Box::new(BufReader::new(File::open(path)?)),
+    })
+}
+
+/// Parses one line of an input file.
+fn input_line(line: &str, ascii: bool) -> Result<Vec<isize>, String> {
+    if ascii {
+        Ok(line.bytes().chain([b'\n']).map(isize::from).collect())
+    } else {
+        parse_values(line)
+    }
+}
+
+/// All input, read lazily so that interactive programs can respond line by line.
+fn input(options: &Options) -> io::Result<Box<dyn Iterator<Item = isize>>> {
+    let mut sources = options.input.clone();
+    if sources.is_empty() {
+        sources.push(Input::File("-".to_string()));
+    }
+    let mut input: Box<dyn Iterator<Item = isize>> = Box::new(std::iter::empty());
+    for source in sources {
+        input = match source {
+            Input::Values(values) => Box::new(input.chain(values)),
+            Input::Text(text) => Box::new(input.chain(input_line(&text, true).unwrap())),
+            Input::File(path) => {
+                let ascii = options.ascii_input;
+                let lines = open(&path)?.lines().map_while(Result::ok);
+                Box::new(input.chain(lines.flat_map(move |line| {
+                    input_line(&line, ascii).unwrap_or_else(|e| {
+                        eprintln!("error: {e}");
+                        std::process::exit(1);
+                    })
+                })))
+            }
+        };
+    }
+    Ok(input)
+}
+
+/// Writes one output value. In ASCII mode, values that aren't ASCII characters get a line of
+/// their own, like the answers most ASCII programs end with.
+fn write_output(w: &mut impl Write, x: isize, ascii: bool) -> io::Result<()> {
+    match u8::try_from(x) {
+        Ok(b) if ascii && b.is_ascii() => write!(w, "{}", b as char),
+        _ => writeln!(w, "{x}"),
+    }
+}
+
+fn execute(
+    program: &mut Intcode,
+    mut input: impl Iterator<Item = isize>,
+    tracer: &mut impl Tracer,
+    mut output: impl FnMut(isize),
+) -> Result<(), IntcodeError> {
+    loop {
+        match program.resume_with(tracer)? {
+            Event::Output(x) => output(x),
+            Event::NeedsInput => match input.next() {
+                Some(x) => program.push_input(x),
+                None => {
+                    let (pc, kind) = (program.pc(), ErrorKind::InputExhausted);
+                    return Err(IntcodeError { pc, word: program.code[pc], kind });
+                }
+            },
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn main_with(options: &Options) -> Result<(), String> {
+    let source = open(&options.program)
+        .and_then(io::read_to_string)
+        .map_err(|e| format!("cannot read {}: {e}", options.program))?;
+    let mut program: Intcode =
+        source.trim().parse().map_err(|e| format!("bad program {}: {e}", options.program))?;
+    for &(addr, value) in &options.patches {
+        program.code[addr] = value;
+    }
+    program.set_step_budget(options.steps);
+    program.set_loop_detection(options.detect_loops);
+
+    let ascii = options.ascii_output;
+    let mut out = stdout().lock();
+    let write = |x| {
+        write_output(&mut out, x, ascii).and_then(|_| out.flush()).expect("failed to write output")
+    };
+    let result = match options.command.as_str() {
+        "run" => execute(&mut program, input(options).map_err(|e| e.to_string())?, &mut (), write),
+        "trace" => {
+            let input = input(options).map_err(|e| e.to_string())?;
+            execute(&mut program, input, &mut TraceWriter(stderr().lock()), write)
+        }
+        "patch" => {
+            if let Some((addr, _)) = program.code.sparse().next() {
+                return Err(format!("patch at {addr} is too far past the program to print"));
+            }
+            println!("{}", program.code.iter().join(","));
+            Ok(())
+        }
+        "disasm" => {
+            print!("{}", program.disassemble());
+            Ok(())
+        }
+        "dot" => {
+            print!("{}", program.cfg().to_dot());
+            Ok(())
+        }
+        "decompile" => {
+            print!("{}", program.cfg().decompile());
+            Ok(())
+        }
+        "transpile" => {
+            print!("{}", program.transpile());
+            Ok(())
+        }
+        cmd => return Err(format!("unknown command `{cmd}`\n\n{USAGE}")),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{USAGE}");
+        return;
+    }
+    let result = parse_args(args).map_err(|e| format!("{e}\n\n{USAGE}"));
+    if let Err(e) = result.and_then(|options| main_with(&options)) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options =
+            parse_args(args("run -i 1,2 -a hi --format ascii -p 0=2 --steps 10 prog.txt")).unwrap();
+        assert_eq!(
+            options,
+            Options {
+                command: "run".to_string(),
+                program: "prog.txt".to_string(),
+                input: vec![Input::Values(vec![1, 2]), Input::Text("hi".to_string())],
+                ascii_output: true,
+                patches: vec![(0, 2)],
+                steps: Some(10),
+                ..Default::default()
+            }
+        );
+        assert!(parse_args(args("run -p 0:2 prog.txt")).is_err());
+        assert!(parse_args(args("run --format hex prog.txt")).is_err());
+        assert!(parse_args(args("run")).is_err());
+        assert_eq!(parse_args(args("disasm -")).unwrap().program, "-");
+    }
+
+    #[test]
+    fn test_execute() {
+        // Echoes one number, then prints "ok" and a value.
+        let mut program = Intcode::parse("3,13,4,13,104,111,104,107,104,10,104,1000,99");
+        let mut out = vec![];
+        let write = |x| write_output(&mut out, x, true).unwrap();
+        assert_eq!(execute(&mut program, [b'>'.into()].into_iter(), &mut (), write), Ok(()));
+        assert_eq!(String::from_utf8(out).unwrap(), ">ok\n1000\n");
+
+        let mut program = Intcode::parse("3,0,99");
+        let e = execute(&mut program, std::iter::empty(), &mut (), |_| {}).unwrap_err();
+        assert_eq!(e.kind, ErrorKind::InputExhausted);
+        assert_eq!(input_line("a", true).unwrap(), [97, 10]);
+        assert_eq!(input_line("1, -2 3", false).unwrap(), [1, -2, 3]);
+    }
+}
@@ -0,0 +1,394 @@
+use itertools::Itertools;
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs::{read_to_string, File};
+use std::io::{stdout, BufReader, BufWriter, Write};
+use utils::{Event, Instruction, Intcode, IntcodeError, Snapshot};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Stop {
+    Step,
+    Breakpoint(usize),
+    Watchpoint {
+        addr: usize,
+        old: isize,
+        new: isize,
+    },
+    NeedsInput,
+    Halted,
+    Error(IntcodeError),
+    /// `continue` ran for `cont_steps` steps without stopping.
+    Interrupted,
+}
+
+/// How long `continue` runs before handing back the prompt, so that a looping program can be
+/// looked at.
+const CONT_STEPS: usize = 10_000_000;
+
+struct Debugger {
+    program: Intcode,
+    output: Vec<isize>,
+    /// Print output as text rather than numbers.
+    ascii: bool,
+    breakpoints: BTreeSet<usize>,
+    // Watched address -> last observed value.
+    watchpoints: BTreeMap<usize, isize>,
+    cont_steps: usize,
+}
+
+impl Debugger {
+    fn new(program: Intcode) -> Self {
+        Self {
+            program,
+            output: vec![],
+            ascii: false,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            cont_steps: CONT_STEPS,
+        }
+    }
+
+    fn peek(&self, addr: usize) -> isize {
+        self.program.code[addr]
+    }
+
+    fn poke(&mut self, addr: usize, value: isize) {
+        self.program.code[addr] = value;
+        if let Some(last) = self.watchpoints.get_mut(&addr) {
+            *last = value;
+        }
+    }
+
+    fn load(&mut self, snapshot: &Snapshot) {
+        self.program.restore(snapshot);
+        for (&addr, last) in self.watchpoints.iter_mut() {
+            *last = self.program.code[addr];
+        }
+    }
+
+    fn watch(&mut self, addr: usize) {
+        self.watchpoints.insert(addr, self.peek(addr));
+    }
+
+    fn step(&mut self) -> Stop {
+        match self.program.step() {
+            Ok(Event::Continue) => {}
+            Ok(Event::Output(x)) => self.output.push(x),
+            Ok(Event::NeedsInput) => return Stop::NeedsInput,
+            Ok(Event::Halted) => return Stop::Halted,
+            Err(e) => return Stop::Error(e),
+        }
+        for (&addr, old) in self.watchpoints.iter_mut() {
+            let new = self.program.code[addr];
+            if new != *old {
+                let old = std::mem::replace(old, new);
+                return Stop::Watchpoint { addr, old, new };
+            }
+        }
+        Stop::Step
+    }
+
+    fn cont(&mut self) -> Stop {
+        for _ in 0..self.cont_steps {
+            match self.step() {
+                Stop::Step if self.breakpoints.contains(&self.program.pc()) => {
+                    return Stop::Breakpoint(self.program.pc());
+                }
+                Stop::Step => {}
+                stop => return stop,
+            }
+        }
+        Stop::Interrupted
+    }
+
+    fn listing(&self, addr: usize, count: usize) -> Vec<String> {
+        let code = &self.program.code;
+        let mut addr = addr;
+        let mut lines = vec![];
+        while lines.len() < count && addr < code.dense_len() {
+            let (text, width) = match Instruction::decode(&code.read::<4>(addr)) {
+                Some(i) => (i.to_string(), i.width()),
+                None => (format!(".data {}", code[addr]), 1),
+            };
+            let marker = if addr == self.program.pc() { "=>" } else { "  " };
+            let mark = if self.breakpoints.contains(&addr) { "*" } else { " " };
+            lines.push(format!("{marker}{mark}{addr:>6}  {text}"));
+            addr += width;
+        }
+        lines
+    }
+
+    fn queue_input(&mut self, line: &str) {
+        match line.split(',').map(|e| e.trim().parse()).collect::<Result<Vec<isize>, _>>() {
+            Ok(values) => self.program.extend_input(values),
+            Err(_) => self.program.extend_input(line.bytes().chain([b'\n']).map(isize::from)),
+        }
+    }
+}
+
+const HELP: &str = "\
+s, step [n]          execute n instructions (default 1)
+c, continue          run until a breakpoint, watchpoint, halt or missing input, or for
+                     10000000 steps
+b, break <addr>      set a breakpoint on pc == addr
+w, watch <addr>      stop whenever the value at addr changes
+d, delete <addr>     remove breakpoint and watchpoint at addr
+i, info              list breakpoints and watchpoints
+r, regs              show pc and ra
+x <addr> [n]         show n memory cells starting at addr
+set <addr> <value>   write value to memory
+l, list [addr] [n]   disassemble n instructions starting at addr (default pc)
+input <line>         queue numbers (comma separated) or an ASCII line as input
+save <file>          save the machine state to a snapshot file
+load <file>          restore the machine state from a snapshot file
+q, quit              exit";
+
+fn parse_args<const N: usize>(args: &[&str]) -> Option<[Option<isize>; N]> {
+    let mut parsed = [None; N];
+    if args.len() > N {
+        return None;
+    }
+    for (p, a) in parsed.iter_mut().zip(args) {
+        *p = Some(a.parse().ok()?);
+    }
+    Some(parsed)
+}
+
+fn report(stop: Stop, dbg: &Debugger) {
+    match stop {
+        Stop::Step => {}
+        Stop::Breakpoint(addr) => println!("Breakpoint at {addr}"),
+        Stop::Watchpoint { addr, old, new } => println!("Watchpoint [{addr}]: {old} -> {new}"),
+        Stop::NeedsInput => println!("Waiting for input"),
+        Stop::Halted => println!("Halted"),
+        Stop::Error(e) => println!("Error: {e}"),
+        Stop::Interrupted => println!("Still running after {} steps", dbg.cont_steps),
+    }
+    if stop != Stop::Halted {
+        println!("{}", dbg.listing(dbg.program.pc(), 1).join("\n"));
+    }
+}
+
+fn flush_output(dbg: &mut Debugger, mut out: impl Write) {
+    if dbg.ascii {
+        for x in dbg.output.drain(..) {
+            match u8::try_from(x) {
+                Ok(b) if b.is_ascii() => write!(out, "{}", b as char).unwrap(),
+                _ => writeln!(out, "<{x}>").unwrap(),
+            }
+        }
+    } else if !dbg.output.is_empty() {
+        writeln!(out, "Output: [{}]", dbg.output.drain(..).join(", ")).unwrap();
+    }
+}
+
+/// Runs `cmd` with its whitespace separated `args`, or the `rest` of the line as typed.
+fn execute(dbg: &mut Debugger, cmd: &str, args: &[&str], rest: &str) -> Result<(), String> {
+    let usage = || format!("invalid arguments for `{cmd}`, try `help`");
+    let addr =
+        |a: Option<isize>| a.ok_or_else(usage).and_then(|a| a.try_into().map_err(|_| usage()));
+    match cmd {
+        "s" | "step" => {
+            let [n] = parse_args(args).ok_or_else(usage)?;
+            let mut stop = Stop::Step;
+            for _ in 0..n.unwrap_or(1) {
+                stop = dbg.step();
+                if stop != Stop::Step {
+                    break;
+                }
+            }
+            flush_output(dbg, stdout());
+            report(stop, dbg);
+        }
+        "c" | "continue" => {
+            let stop = dbg.cont();
+            flush_output(dbg, stdout());
+            report(stop, dbg);
+        }
+        "b" | "break" => {
+            let [a] = parse_args(args).ok_or_else(usage)?;
+            dbg.breakpoints.insert(addr(a)?);
+        }
+        "w" | "watch" => {
+            let [a] = parse_args(args).ok_or_else(usage)?;
+            dbg.watch(addr(a)?);
+        }
+        "d" | "delete" => {
+            let [a] = parse_args(args).ok_or_else(usage)?;
+            let a = addr(a)?;
+            dbg.breakpoints.remove(&a);
+            dbg.watchpoints.remove(&a);
+        }
+        "i" | "info" => {
+            println!("Breakpoints: {:?}", dbg.breakpoints);
+            println!("Watchpoints: {:?}", dbg.watchpoints);
+            println!("Pending input: {:?}", dbg.program.pending_input());
+        }
+        "r" | "regs" => println!("pc={} ra={}", dbg.program.pc(), dbg.program.ra()),
+        "x" => {
+            let [a, n] = parse_args(args).ok_or_else(usage)?;
+            let a = addr(a)?;
+            let values = (a..a + n.unwrap_or(1).max(0) as usize).map(|i| dbg.peek(i));
+            println!("[{a}]: {}", values.format(", "));
+        }
+        "set" => {
+            let [a, v] = parse_args(args).ok_or_else(usage)?;
+            dbg.poke(addr(a)?, v.ok_or_else(usage)?);
+        }
+        "l" | "list" => {
+            let [a, n] = parse_args(args).ok_or_else(usage)?;
+            let a = a.map_or(Ok(dbg.program.pc()), |a| addr(Some(a)))?;
+            println!("{}", dbg.listing(a, n.unwrap_or(10).max(0) as usize).join("\n"));
+        }
+        "input" => dbg.queue_input(rest),
+        "save" => {
+            let &[path] = args else { return Err(usage()) };
+            let file = File::create(path).map_err(|e| e.to_string())?;
+            dbg.program.snapshot().save(BufWriter::new(file)).map_err(|e| e.to_string())?;
+        }
+        "load" => {
+            let &[path] = args else { return Err(usage()) };
+            let file = File::open(path).map_err(|e| e.to_string())?;
+            let snapshot = Snapshot::load(BufReader::new(file)).map_err(|e| e.to_string())?;
+            dbg.load(&snapshot);
+            report(Stop::Step, dbg);
+        }
+        "h" | "help" => println!("{HELP}"),
+        _ => return Err(format!("unknown command `{cmd}`, try `help`")),
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let ascii = args.iter().position(|a| a == "--ascii").map(|i| args.remove(i)).is_some();
+    let Some(path) = args.first() else {
+        eprintln!("Usage: intdbg [--ascii] <program> [input-script]");
+        std::process::exit(1);
+    };
+    let program: Intcode = read_to_string(path).unwrap().trim().parse().unwrap();
+    let mut dbg = Debugger::new(program);
+    dbg.ascii = ascii;
+    if let Some(script) = args.get(1) {
+        for line in read_to_string(script).unwrap().lines() {
+            dbg.queue_input(line);
+        }
+    }
+
+    let mut rl = DefaultEditor::new().unwrap();
+    let mut last = String::new();
+    println!("{}", dbg.listing(dbg.program.pc(), 1).join("\n"));
+    loop {
+        let line = match rl.readline("(intdbg) ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => panic!("{e:?}"),
+        };
+        // An empty line repeats the last command.
+        let line = if line.trim().is_empty() { last.clone() } else { line };
+        let _ = rl.add_history_entry(&line);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&cmd, args)) = words.split_first() else {
+            continue;
+        };
+        if cmd == "q" || cmd == "quit" {
+            break;
+        }
+        let rest = line.trim_start()[cmd.len()..].trim_start();
+        if let Err(e) = execute(&mut dbg, cmd, args, rest) {
+            println!("{e}");
+        }
+        last = line;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn countdown() -> Debugger {
+        let program = Intcode::assemble(
+            r"
+                in [n]
+            loop:
+                out [n]
+                add [n], -1, [n]
+                jit [n], loop
+                hlt
+            n:  .data 0
+            ",
+        )
+        .unwrap();
+        Debugger::new(program)
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut dbg = countdown();
+        assert_eq!(dbg.cont(), Stop::NeedsInput);
+        dbg.queue_input("3");
+        dbg.breakpoints.insert(4);
+        assert_eq!(dbg.cont(), Stop::Breakpoint(4));
+        assert_eq!(dbg.output, [3]);
+        assert_eq!(dbg.cont(), Stop::Breakpoint(4));
+        assert_eq!(dbg.output, [3, 2]);
+        dbg.breakpoints.clear();
+        assert_eq!(dbg.cont(), Stop::Halted);
+        assert_eq!(dbg.output, [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut dbg = countdown();
+        dbg.queue_input("2");
+        dbg.watch(12);
+        assert_eq!(dbg.cont(), Stop::Watchpoint { addr: 12, old: 0, new: 2 });
+        assert_eq!(dbg.program.pc(), 2);
+        assert_eq!(dbg.cont(), Stop::Watchpoint { addr: 12, old: 2, new: 1 });
+        dbg.poke(12, 1000);
+        assert_eq!(dbg.cont(), Stop::Watchpoint { addr: 12, old: 1000, new: 999 });
+        assert_eq!(dbg.output, [2, 1000]);
+
+        let snapshot = dbg.program.snapshot();
+        assert_eq!(dbg.cont(), Stop::Watchpoint { addr: 12, old: 999, new: 998 });
+        dbg.load(&snapshot);
+        assert_eq!(dbg.cont(), Stop::Watchpoint { addr: 12, old: 999, new: 998 });
+    }
+
+    #[test]
+    fn test_interrupted() {
+        let mut dbg = Debugger::new(Intcode::assemble("spin: jif 0, spin").unwrap());
+        dbg.cont_steps = 1000;
+        assert_eq!(dbg.cont(), Stop::Interrupted);
+        dbg.breakpoints.insert(0);
+        assert_eq!(dbg.cont(), Stop::Breakpoint(0));
+    }
+
+    #[test]
+    fn test_ascii_input() {
+        let mut dbg = countdown();
+        dbg.queue_input("1, -2");
+        dbg.queue_input("go");
+        assert_eq!(dbg.program.pending_input(), &[1, -2, b'g'.into(), b'o'.into(), b'\n'.into()]);
+
+        let mut dbg = countdown();
+        execute(&mut dbg, "input", &["a", "b"], "a  b").unwrap();
+        let line: Vec<isize> = "a  b\n".bytes().map(isize::from).collect();
+        assert_eq!(dbg.program.pending_input(), &line);
+    }
+
+    #[test]
+    fn test_flush_output() {
+        let mut dbg = countdown();
+        let mut out = vec![];
+        dbg.output = vec![1, 2];
+        flush_output(&mut dbg, &mut out);
+        dbg.ascii = true;
+        dbg.output = vec![72, 105, 10, 1000];
+        flush_output(&mut dbg, &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "Output: [1, 2]\nHi\n<1000>\n");
+        assert!(dbg.output.is_empty());
+    }
+}
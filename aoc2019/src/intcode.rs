@@ -1,66 +1,163 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::ParseIntError;
 use std::str::FromStr;
 use std::sync::mpsc;
 
+mod ascii;
+mod asm;
+mod cfg;
+mod limits;
+mod memory;
+mod snapshot;
+mod trace;
+mod transcript;
+mod transpile;
+mod word;
+
+pub use ascii::*;
+pub use asm::*;
+pub use cfg::*;
+pub use memory::*;
+pub use snapshot::*;
+pub use trace::*;
+pub use transcript::*;
+pub use word::*;
+
 #[derive(Debug, Default, Clone, Hash, Eq, PartialEq)]
-pub struct Intcode {
-    pub code: Vec<isize>,
+pub struct Intcode<W: Word = isize> {
+    pub code: Memory<W>,
 
     pc: usize,
-    ra: isize,
+    ra: W,
+    input: VecDeque<W>,
+    limits: limits::Limits<W>,
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum ErrorKind<W = isize> {
+    BadOpcode,
+    BadMode,
+    ImmediateWrite,
+    Truncated,
+    NegativeAddress(W),
+    /// An arithmetic result or an address doesn't fit.
+    Overflow,
+    InputExhausted,
+    /// The step budget ran out. The machine is untouched and can resume with a new budget.
+    BudgetExhausted,
+    /// The machine returned to an earlier state without doing any I/O.
+    InfiniteLoop,
 }
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
-enum Param {
-    Imme(isize),
-    Addr(isize),
-    Rela(isize),
+pub struct IntcodeError<W = isize> {
+    pub pc: usize,
+    /// The instruction word at `pc`.
+    pub word: W,
+    pub kind: ErrorKind<W>,
+}
+
+impl<W: Word> std::fmt::Display for IntcodeError<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (pc, word) = (self.pc, self.word);
+        match self.kind {
+            ErrorKind::BadOpcode => write!(f, "unknown opcode {word} at pc={pc}"),
+            ErrorKind::BadMode => write!(f, "bad parameter mode in {word} at pc={pc}"),
+            ErrorKind::ImmediateWrite => write!(f, "immediate write operand in {word} at pc={pc}"),
+            ErrorKind::Truncated => write!(f, "truncated instruction {word} at pc={pc}"),
+            ErrorKind::NegativeAddress(a) => write!(f, "negative address {a} in {word} at pc={pc}"),
+            ErrorKind::Overflow => write!(f, "overflow in {word} at pc={pc}"),
+            ErrorKind::InputExhausted => write!(f, "input exhausted at pc={pc}"),
+            ErrorKind::BudgetExhausted => write!(f, "step budget exhausted at pc={pc}"),
+            ErrorKind::InfiniteLoop => write!(f, "infinite loop at pc={pc}"),
+        }
+    }
 }
 
+impl<W: Word> std::error::Error for IntcodeError<W> {}
+
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
-enum Instruction {
-    Add(Param, Param, Param),
-    Mul(Param, Param, Param),
-    Input(Param),
-    Output(Param),
-    Jit(Param, Param),
-    Jif(Param, Param),
-    Tlt(Param, Param, Param),
-    Teq(Param, Param, Param),
-    Rela(Param),
+pub enum Param<W = isize> {
+    Imme(W),
+    Addr(W),
+    Rela(W),
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Instruction<W = isize> {
+    Add(Param<W>, Param<W>, Param<W>),
+    Mul(Param<W>, Param<W>, Param<W>),
+    Input(Param<W>),
+    Output(Param<W>),
+    Jit(Param<W>, Param<W>),
+    Jif(Param<W>, Param<W>),
+    Tlt(Param<W>, Param<W>, Param<W>),
+    Teq(Param<W>, Param<W>, Param<W>),
+    Rela(Param<W>),
     Halt,
 }
 
-impl Instruction {
-    fn parse(code: &[isize]) -> Self {
-        let mut params = code.iter().skip(1).copied().scan(code[0] / 100, |st, p| {
-            let param = match *st % 10 {
-                0 => Param::Addr(p),
-                1 => Param::Imme(p),
-                2 => Param::Rela(p),
-                m => panic!("Unexpected parameter mode {m}"),
+/// Small non-negative words, such as opcodes and modes.
+fn small<W: Word>(x: W) -> Option<usize> {
+    x.try_into().ok()
+}
+
+/// The highest address, leaving room for the widest instruction after it.
+const MAX_ADDR: usize = usize::MAX - 4;
+
+/// Converts an address, which must be non-negative and at most [`MAX_ADDR`].
+fn to_addr<W: Word>(a: W) -> Result<usize, ErrorKind<W>> {
+    match a.try_into() {
+        Ok(a) if a <= MAX_ADDR => Ok(a),
+        Err(_) if a < W::ZERO => Err(ErrorKind::NegativeAddress(a)),
+        _ => Err(ErrorKind::Overflow),
+    }
+}
+
+impl<W: Word> Instruction<W> {
+    pub fn parse(code: &[W]) -> Result<Self, ErrorKind<W>> {
+        let (&op, rest) = code.split_first().ok_or(ErrorKind::Truncated)?;
+        let mut modes = op / 100.into();
+        let mut rest = rest.iter().copied();
+        let mut param = || {
+            let p = rest.next().ok_or(ErrorKind::Truncated)?;
+            let param = match small(modes % 10.into()) {
+                Some(0) => Param::Addr(p),
+                Some(1) => Param::Imme(p),
+                Some(2) => Param::Rela(p),
+                _ => return Err(ErrorKind::BadMode),
             };
-            *st /= 10;
-            Some(param)
-        });
-        let mut param = || params.next().unwrap();
-        match code[0] % 100 {
-            1 => Self::Add(param(), param(), param()),
-            2 => Self::Mul(param(), param(), param()),
-            3 => Self::Input(param()),
-            4 => Self::Output(param()),
-            5 => Self::Jit(param(), param()),
-            6 => Self::Jif(param(), param()),
-            7 => Self::Tlt(param(), param(), param()),
-            8 => Self::Teq(param(), param(), param()),
-            9 => Self::Rela(param()),
+            modes = modes / 10.into();
+            Ok(param)
+        };
+        let Some(opcode) = small(op % 100.into()) else {
+            return Err(ErrorKind::BadOpcode);
+        };
+        let instruction = match opcode {
+            1 => Self::Add(param()?, param()?, param()?),
+            2 => Self::Mul(param()?, param()?, param()?),
+            3 => Self::Input(param()?),
+            4 => Self::Output(param()?),
+            5 => Self::Jit(param()?, param()?),
+            6 => Self::Jif(param()?, param()?),
+            7 => Self::Tlt(param()?, param()?, param()?),
+            8 => Self::Teq(param()?, param()?, param()?),
+            9 => Self::Rela(param()?),
             99 => Self::Halt,
-            o => panic!("Unexpected opcode {o}"),
+            _ => return Err(ErrorKind::BadOpcode),
+        };
+        match instruction.dest() {
+            Some(Param::Imme(_)) => Err(ErrorKind::ImmediateWrite),
+            _ => Ok(instruction),
         }
     }
 
-    fn width(&self) -> usize {
+    pub fn decode(code: &[W]) -> Option<Self> {
+        Self::parse(code).ok()
+    }
+
+    pub fn width(&self) -> usize {
         match self {
             Self::Add(..) | Self::Mul(..) | Self::Tlt(..) | Self::Teq(..) => 4,
             Self::Jit(..) | Self::Jif(..) => 3,
@@ -68,13 +165,127 @@ impl Instruction {
             Self::Halt => 1,
         }
     }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Add(..) => "add",
+            Self::Mul(..) => "mul",
+            Self::Input(..) => "in",
+            Self::Output(..) => "out",
+            Self::Jit(..) => "jit",
+            Self::Jif(..) => "jif",
+            Self::Tlt(..) => "lt",
+            Self::Teq(..) => "eq",
+            Self::Rela(..) => "arb",
+            Self::Halt => "hlt",
+        }
+    }
+
+    pub fn params(&self) -> Vec<Param<W>> {
+        match *self {
+            Self::Add(a, b, c) | Self::Mul(a, b, c) | Self::Tlt(a, b, c) | Self::Teq(a, b, c) => {
+                vec![a, b, c]
+            }
+            Self::Jit(a, b) | Self::Jif(a, b) => vec![a, b],
+            Self::Input(a) | Self::Output(a) | Self::Rela(a) => vec![a],
+            Self::Halt => vec![],
+        }
+    }
+
+    /// The operand this instruction writes to, if any.
+    pub fn dest(&self) -> Option<Param<W>> {
+        match *self {
+            Self::Add(_, _, c) | Self::Mul(_, _, c) | Self::Tlt(_, _, c) | Self::Teq(_, _, c) => {
+                Some(c)
+            }
+            Self::Input(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn opcode(&self) -> W {
+        let opcode: i8 = match self {
+            Self::Add(..) => 1,
+            Self::Mul(..) => 2,
+            Self::Input(..) => 3,
+            Self::Output(..) => 4,
+            Self::Jit(..) => 5,
+            Self::Jif(..) => 6,
+            Self::Tlt(..) => 7,
+            Self::Teq(..) => 8,
+            Self::Rela(..) => 9,
+            Self::Halt => 99,
+        };
+        opcode.into()
+    }
+
+    pub fn encode(&self) -> Vec<W> {
+        let params = self.params();
+        let modes = params.iter().rev().fold(W::ZERO, |m, p| m * 10.into() + p.mode());
+        std::iter::once(modes * 100.into() + self.opcode())
+            .chain(params.iter().map(|p| p.value()))
+            .collect()
+    }
+
+    /// The statically known destination of a conditional jump.
+    pub fn jump_target(&self) -> Option<usize> {
+        match *self {
+            Self::Jit(_, Param::Imme(t)) | Self::Jif(_, Param::Imme(t)) => t.try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl<W: Word> Param<W> {
+    fn mode(&self) -> W {
+        match self {
+            Self::Addr(_) => 0.into(),
+            Self::Imme(_) => 1.into(),
+            Self::Rela(_) => 2.into(),
+        }
+    }
+
+    fn value(&self) -> W {
+        match *self {
+            Self::Imme(a) | Self::Addr(a) | Self::Rela(a) => a,
+        }
+    }
+}
+
+impl<W: Word> std::fmt::Display for Param<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Imme(a) => write!(f, "{a}"),
+            Self::Addr(a) => write!(f, "[{a}]"),
+            Self::Rela(a) if a >= W::ZERO => write!(f, "[ra+{a}]"),
+            Self::Rela(a) => write!(f, "[ra{a}]"),
+        }
+    }
+}
+
+impl<W: Word> std::fmt::Display for Instruction<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for (i, p) in self.params().iter().enumerate() {
+            write!(f, "{}{p}", if i == 0 { " " } else { ", " })?;
+        }
+        Ok(())
+    }
 }
 
-impl FromStr for Intcode {
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Event<W = isize> {
+    Continue,
+    Output(W),
+    NeedsInput,
+    Halted,
+}
+
+impl<W: Word> FromStr for Intcode<W> {
     type Err = ParseIntError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let code = s.split(',').map(|e| e.trim().parse()).collect::<Result<Vec<_>, _>>()?;
+        let code = s.split(',').map(|e| e.trim().parse()).collect::<Result<_, _>>()?;
         Ok(Self { code, ..Default::default() })
     }
 }
@@ -83,101 +294,361 @@ impl Intcode {
     pub fn parse(s: &str) -> Self {
         s.parse().unwrap()
     }
+}
 
-    fn get(&self, p: Param) -> isize {
+impl<W: Word> Intcode<W> {
+    fn addr(&self, p: Param<W>) -> Result<usize, ErrorKind<W>> {
         match p {
-            Param::Imme(a) => a,
-            Param::Addr(a) => self.code.get(a as usize).copied().unwrap_or(0),
-            Param::Rela(a) => self.code.get((self.ra + a) as usize).copied().unwrap_or(0),
+            Param::Imme(_) => Err(ErrorKind::ImmediateWrite),
+            Param::Addr(a) => to_addr(a),
+            Param::Rela(a) => to_addr(self.ra.checked_add(a).ok_or(ErrorKind::Overflow)?),
         }
     }
 
-    fn get_or_resize(&mut self, idx: usize) -> &mut isize {
-        if idx >= self.code.len() {
-            self.code.resize(idx + 1, 0);
+    fn get(&self, p: Param<W>) -> Result<W, ErrorKind<W>> {
+        match p {
+            Param::Imme(a) => Ok(a),
+            _ => Ok(self.code[self.addr(p)?]),
         }
-        &mut self.code[idx]
     }
 
-    fn get_mut(&mut self, p: Param) -> &mut isize {
-        match p {
-            Param::Imme(_) => panic!("write operand cannot be an immediate value {p:?}"),
-            Param::Addr(a) => self.get_or_resize(a as usize),
-            Param::Rela(a) => self.get_or_resize((self.ra + a) as usize),
-        }
+    fn set(&mut self, p: Param<W>, value: W) -> Result<(), ErrorKind<W>> {
+        let idx = self.addr(p)?;
+        self.code[idx] = value;
+        Ok(())
+    }
+
+    fn jump(&mut self, target: W) -> Result<Event<W>, ErrorKind<W>> {
+        self.pc = to_addr(target)?;
+        Ok(Event::Continue)
+    }
+
+    fn advance(&mut self, width: usize) -> Result<(), ErrorKind<W>> {
+        self.pc =
+            self.pc.checked_add(width).filter(|&pc| pc <= MAX_ADDR).ok_or(ErrorKind::Overflow)?;
+        Ok(())
+    }
+
+    /// The instruction at pc, with memory past the end of `code` read as zero.
+    fn fetch(&self) -> Result<Instruction<W>, ErrorKind<W>> {
+        Instruction::parse(&self.code.read::<4>(self.pc))
     }
 
     pub fn halted(&self) -> bool {
-        matches!(Instruction::parse(&self.code[self.pc..]), Instruction::Halt)
+        matches!(self.fetch(), Ok(Instruction::Halt))
     }
 
-    fn step(&mut self, mut input: impl Iterator<Item = isize>) -> Option<isize> {
-        loop {
-            let instruction = Instruction::parse(&self.code[self.pc..]);
-            match instruction {
-                Instruction::Add(a, b, c) => *self.get_mut(c) = self.get(a) + self.get(b),
-                Instruction::Mul(a, b, c) => *self.get_mut(c) = self.get(a) * self.get(b),
-                Instruction::Input(a) => *self.get_mut(a) = input.next().expect("input exhausted"),
-                Instruction::Output(a) => {
-                    self.pc += instruction.width();
-                    return Some(self.get(a));
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn ra(&self) -> W {
+        self.ra
+    }
+
+    pub fn push_input(&mut self, x: W) {
+        self.input.push_back(x);
+    }
+
+    pub fn extend_input(&mut self, seq: impl IntoIterator<Item = W>) {
+        self.input.extend(seq);
+    }
+
+    pub fn pending_input(&self) -> &VecDeque<W> {
+        &self.input
+    }
+
+    /// Executes a single instruction. An input instruction with no pending input leaves the machine
+    /// untouched and reports [`Event::NeedsInput`].
+    pub fn step(&mut self) -> Result<Event<W>, IntcodeError<W>> {
+        self.step_with(&mut ())
+    }
+
+    /// Like [`Intcode::step`], but reports the executed instruction to `tracer`.
+    pub fn step_with<T: Tracer<W>>(&mut self, tracer: &mut T) -> Result<Event<W>, IntcodeError<W>> {
+        self.check_budget().map_err(|kind| self.error(kind))?;
+        let (pc, pending) = (self.pc, self.input.len());
+        let entry = if T::ENABLED { self.trace_entry() } else { None };
+        let event = self.exec().map_err(|kind| self.error(kind))?;
+        self.check_limits(pc, pending, &event).map_err(|kind| self.error(kind))?;
+        if let Some(mut entry) = entry.filter(|_| event != Event::NeedsInput) {
+            if let Some((addr, value)) = entry.write.as_mut() {
+                *value = self.code[*addr];
+            }
+            tracer.record(&entry);
+        }
+        Ok(event)
+    }
+
+    fn error(&self, kind: ErrorKind<W>) -> IntcodeError<W> {
+        IntcodeError { pc: self.pc, word: self.code[self.pc], kind }
+    }
+
+    fn exec(&mut self) -> Result<Event<W>, ErrorKind<W>> {
+        let instruction = self.code.decode(self.pc)?;
+        match instruction {
+            Instruction::Add(a, b, c) => {
+                let x = self.get(a)?.checked_add(self.get(b)?).ok_or(ErrorKind::Overflow)?;
+                self.set(c, x)?
+            }
+            Instruction::Mul(a, b, c) => {
+                let x = self.get(a)?.checked_mul(self.get(b)?).ok_or(ErrorKind::Overflow)?;
+                self.set(c, x)?
+            }
+            Instruction::Input(a) => match self.input.front() {
+                Some(&x) => {
+                    self.set(a, x)?;
+                    self.input.pop_front();
                 }
-                Instruction::Jit(a, b) => {
-                    if self.get(a) != 0 {
-                        self.pc = self.get(b) as usize;
-                        continue;
-                    }
+                None => return Ok(Event::NeedsInput),
+            },
+            Instruction::Output(a) => {
+                let x = self.get(a)?;
+                self.advance(instruction.width())?;
+                return Ok(Event::Output(x));
+            }
+            Instruction::Jit(a, b) => {
+                if self.get(a)? != W::ZERO {
+                    return self.jump(self.get(b)?);
                 }
-                Instruction::Jif(a, b) => {
-                    if self.get(a) == 0 {
-                        self.pc = self.get(b) as usize;
-                        continue;
-                    }
+            }
+            Instruction::Jif(a, b) => {
+                if self.get(a)? == W::ZERO {
+                    return self.jump(self.get(b)?);
                 }
-                Instruction::Tlt(a, b, c) => *self.get_mut(c) = (self.get(a) < self.get(b)).into(),
-                Instruction::Teq(a, b, c) => *self.get_mut(c) = (self.get(a) == self.get(b)).into(),
-                Instruction::Rela(a) => self.ra += self.get(a),
-                Instruction::Halt => return None,
             }
-            self.pc += instruction.width();
+            Instruction::Tlt(a, b, c) => self.set(c, (self.get(a)? < self.get(b)?).into())?,
+            Instruction::Teq(a, b, c) => self.set(c, (self.get(a)? == self.get(b)?).into())?,
+            Instruction::Rela(a) => {
+                self.ra = self.ra.checked_add(self.get(a)?).ok_or(ErrorKind::Overflow)?
+            }
+            Instruction::Halt => return Ok(Event::Halted),
         }
+        self.advance(instruction.width())?;
+        Ok(Event::Continue)
     }
 
-    pub fn run<'a>(
+    /// Steps until the machine produces output, runs out of input or halts.
+    pub fn resume(&mut self) -> Result<Event<W>, IntcodeError<W>> {
+        self.resume_with(&mut ())
+    }
+
+    pub fn resume_with<T: Tracer<W>>(
+        &mut self,
+        tracer: &mut T,
+    ) -> Result<Event<W>, IntcodeError<W>> {
+        loop {
+            match self.step_with(tracer)? {
+                Event::Continue => {}
+                event => return Ok(event),
+            }
+        }
+    }
+
+    /// Like [`Intcode::run`], but reports faults instead of panicking. Iteration ends after the
+    /// first error.
+    pub fn try_run<'a>(
         &'a mut self,
-        input: impl IntoIterator<Item = isize> + 'a,
-    ) -> impl Iterator<Item = isize> + 'a {
+        input: impl IntoIterator<Item = W> + 'a,
+    ) -> impl Iterator<Item = Result<W, IntcodeError<W>>> + 'a {
         let mut input = input.into_iter();
-        std::iter::from_fn(move || self.step(input.by_ref()))
+        let mut failed = false;
+        std::iter::from_fn(move || {
+            while !failed {
+                match self.resume() {
+                    Ok(Event::Output(x)) => return Some(Ok(x)),
+                    Ok(Event::NeedsInput) => match input.next() {
+                        Some(x) => self.push_input(x),
+                        None => {
+                            failed = true;
+                            return Some(Err(self.error(ErrorKind::InputExhausted)));
+                        }
+                    },
+                    Ok(_) => return None,
+                    Err(e) => {
+                        failed = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            None
+        })
     }
 
-    pub fn deferred_run(&mut self) -> Deferred<'_> {
+    pub fn run<'a>(
+        &'a mut self,
+        input: impl IntoIterator<Item = W> + 'a,
+    ) -> impl Iterator<Item = W> + 'a {
+        self.try_run(input).map(|r| r.unwrap_or_else(|e| panic!("{e}")))
+    }
+
+    pub fn deferred_run(&mut self) -> Deferred<'_, W> {
         Deferred::run(self)
     }
+
+    /// Fills the decode cache for everything that disassembles as code, so that clones of this
+    /// machine start out with decoded instructions.
+    pub fn predecode(&mut self) {
+        let code = self.code.to_vec();
+        for (addr, item) in sweep(&code, |_| false) {
+            if let Item::Code(_) = item {
+                let _ = self.code.decode(addr);
+            }
+        }
+    }
+
+    pub fn disassemble(&self) -> Disassembly<W> {
+        disassemble(&self.code.to_vec())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Item<W = isize> {
+    Code(Instruction<W>),
+    Data(W),
+}
+
+impl<W: Word> Item<W> {
+    fn width(&self) -> usize {
+        match self {
+            Self::Code(instruction) => instruction.width(),
+            Self::Data(_) => 1,
+        }
+    }
 }
 
-pub struct Deferred<'a> {
-    output: Box<RefCell<dyn Iterator<Item = isize> + 'a>>,
-    tx: mpsc::Sender<isize>,
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Line<W = isize> {
+    pub addr: usize,
+    pub label: Option<String>,
+    pub item: Item<W>,
 }
 
-impl<'a> Deferred<'a> {
-    fn run(program: &'a mut Intcode) -> Self {
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Disassembly<W = isize> {
+    pub lines: Vec<Line<W>>,
+}
+
+/// Linear sweep over `code`. Words that don't decode canonically, or instructions that would
+/// straddle a `barrier` address, are emitted as data.
+fn sweep<'a, W: Word>(
+    code: &'a [W],
+    barrier: impl Fn(&usize) -> bool + 'a,
+) -> impl Iterator<Item = (usize, Item<W>)> + 'a {
+    let mut addr = 0;
+    std::iter::from_fn(move || {
+        if addr >= code.len() {
+            return None;
+        }
+        let item = match Instruction::decode(&code[addr..]) {
+            Some(i)
+                if !(addr + 1..addr + i.width()).any(|a| barrier(&a))
+                    && i.encode() == code[addr..addr + i.width()] =>
+            {
+                Item::Code(i)
+            }
+            _ => Item::Data(code[addr]),
+        };
+        addr += item.width();
+        Some((addr - item.width(), item))
+    })
+}
+
+pub fn disassemble<W: Word>(code: &[W]) -> Disassembly<W> {
+    let targets: HashSet<usize> = sweep(code, |_| false)
+        .filter_map(|(_, item)| match item {
+            Item::Code(i) => i.jump_target(),
+            Item::Data(_) => None,
+        })
+        .filter(|&t| t < code.len())
+        .collect();
+    let lines = sweep(code, |a| targets.contains(a))
+        .map(|(addr, item)| Line {
+            addr,
+            label: targets.contains(&addr).then(|| format!("L{addr}")),
+            item,
+        })
+        .collect();
+    Disassembly { lines }
+}
+
+impl<W: Word> std::fmt::Display for Disassembly<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let labels: HashMap<usize, &str> =
+            self.lines.iter().filter_map(|l| Some((l.addr, l.label.as_deref()?))).collect();
+        for line in self.lines.iter() {
+            if let Some(label) = &line.label {
+                writeln!(f, "{label}:")?;
+            }
+            let text = match line.item {
+                Item::Code(i) => match (i, i.jump_target().and_then(|t| labels.get(&t))) {
+                    (Instruction::Jit(a, _) | Instruction::Jif(a, _), Some(label)) => {
+                        format!("{} {a}, {label}", i.mnemonic())
+                    }
+                    _ => i.to_string(),
+                },
+                Item::Data(v) => format!(".data {v}"),
+            };
+            writeln!(f, "    {text:<24} ; {}", line.addr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a program as its output is asked for, with input sent along the way. When the program
+/// waits for input that hasn't been sent yet, the output ends instead of blocking, and picks up
+/// again once more input is sent.
+pub struct Deferred<'a, W = isize> {
+    output: Box<RefCell<dyn Iterator<Item = W> + 'a>>,
+    tx: mpsc::Sender<W>,
+}
+
+impl<'a, W: Word> Deferred<'a, W> {
+    fn run(program: &'a mut Intcode<W>) -> Self {
+        Self::new(program, |_, _| {})
+    }
+
+    /// `observe` sees the event of every step, and the value fed to the program when it asked
+    /// for input.
+    fn new(program: &'a mut Intcode<W>, mut observe: impl FnMut(Event<W>, Option<W>) + 'a) -> Self {
         let (tx, rx) = mpsc::channel();
-        Self { output: Box::new(RefCell::new(program.run(rx))), tx }
+        let mut halted = false;
+        let output = std::iter::from_fn(move || {
+            while !halted {
+                let event = program.step().unwrap_or_else(|e| panic!("{e}"));
+                match event {
+                    Event::Continue => observe(event, None),
+                    Event::Output(x) => {
+                        observe(event, None);
+                        return Some(x);
+                    }
+                    Event::NeedsInput => {
+                        let x = rx.try_recv().ok();
+                        observe(event, x);
+                        program.push_input(x?);
+                    }
+                    Event::Halted => {
+                        observe(event, None);
+                        halted = true;
+                    }
+                }
+            }
+            None
+        });
+        Self { output: Box::new(RefCell::new(output)), tx }
     }
 
-    pub fn send(&self, x: isize) {
+    pub fn send(&self, x: W) {
         self.tx.send(x).unwrap();
     }
 
-    pub fn send_seq(&self, seq: impl IntoIterator<Item = isize>) {
+    pub fn send_seq(&self, seq: impl IntoIterator<Item = W>) {
         for s in seq {
             self.send(s);
         }
     }
 
-    pub fn iter<'b>(&'b self) -> impl Iterator<Item = isize> + 'b + use<'b, 'a> {
+    pub fn iter<'b>(&'b self) -> impl Iterator<Item = W> + 'b + use<'b, 'a, W> {
         let mut iter = self.output.borrow_mut();
         std::iter::from_fn(move || iter.next())
     }
@@ -185,6 +656,13 @@ impl<'a> Deferred<'a> {
 
 #[macro_export]
 macro_rules! assert_intcode {
+    ($program:expr, transcript = $path:expr) => {{
+        let file = std::fs::File::open($path).expect("cannot open transcript");
+        let transcript = $crate::Transcript::load(std::io::BufReader::new(file)).unwrap();
+        if let Err(e) = transcript.replay(&mut Intcode::parse($program)) {
+            panic!("{e}");
+        }
+    }};
     ($program:expr, $expect:expr) => {
         itertools::assert_equal(Intcode::parse($program).run(std::iter::empty()), $expect)
     };
@@ -254,4 +732,125 @@ mod tests {
         assert_intcode!("1102,34915192,34915192,7,4,7,99,0", [1219070632396864]);
         assert_intcode!("104,1125899906842624,99", [1125899906842624]);
     }
+
+    #[test]
+    fn test_sparse_memory() {
+        let mut program = Intcode::parse("1101,5,6,1000000000000,4,1000000000000,99");
+        itertools::assert_equal(program.run([]), [11]);
+        let stats = program.code.stats();
+        assert_eq!((stats.dense_pages, stats.sparse_pages), (1, 1));
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut program = Intcode::parse("3,9,1002,9,3,9,4,9,99,0");
+        assert_eq!(program.resume(), Ok(Event::NeedsInput));
+        assert_eq!(program.resume(), Ok(Event::NeedsInput));
+        program.push_input(33);
+        assert_eq!(program.resume(), Ok(Event::Output(99)));
+        assert_eq!(program.resume(), Ok(Event::Halted));
+        assert!(program.halted());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |program, input: &[isize]| {
+            Intcode::parse(program).try_run(input.to_vec()).find_map(Result::err).unwrap()
+        };
+        let e = |pc, word, kind| IntcodeError { pc, word, kind };
+        assert_eq!(error("104,1,42", &[]), e(2, 42, ErrorKind::BadOpcode));
+        assert_eq!(error("304,0,99", &[]), e(0, 304, ErrorKind::BadMode));
+        assert_eq!(error("11101,1,1,1,99", &[]), e(0, 11101, ErrorKind::ImmediateWrite));
+        assert_eq!(error("4,-1,99", &[]), e(0, 4, ErrorKind::NegativeAddress(-1)));
+        assert_eq!(error("109,-5,204,2,99", &[]), e(2, 204, ErrorKind::NegativeAddress(-3)));
+        assert_eq!(error("1106,0,-7", &[]), e(0, 1106, ErrorKind::NegativeAddress(-7)));
+        assert_eq!(error("3,0,3,0,99", &[1]), e(2, 3, ErrorKind::InputExhausted));
+    }
+
+    #[test]
+    fn test_overflow() {
+        fn error<W: Word>(program: &str) -> (usize, ErrorKind<W>) {
+            let mut program: Intcode<W> = program.parse().unwrap();
+            let e = program.try_run([]).find_map(Result::err).unwrap();
+            (e.pc, e.kind)
+        }
+        assert_eq!(error::<i32>("1102,34915192,34915192,7,4,7,99,0"), (0, ErrorKind::Overflow));
+        let program = "1102,1125899906842624,1125899906842624,7,4,7,99,0";
+        assert_eq!(error::<i64>(program), (0, ErrorKind::Overflow));
+        assert_eq!(error::<i64>("109,9223372036854775807,109,1,99"), (2, ErrorKind::Overflow));
+        let program = "4,100000000000000000000000,99";
+        assert_eq!(error::<i128>(program), (0, ErrorKind::Overflow));
+        assert_eq!(error::<i128>("4,-1,99"), (0, ErrorKind::NegativeAddress(-1)));
+        // Addresses that fit in usize, but leave no room for the instruction there.
+        assert_eq!(error::<i128>("1106,0,18446744073709551615"), (0, ErrorKind::Overflow));
+        assert_eq!(error::<i128>("1105,1,18446744073709551614"), (0, ErrorKind::Overflow));
+        let program = "1101,1,1,18446744073709551615,99";
+        assert_eq!(error::<i128>(program), (0, ErrorKind::Overflow));
+    }
+
+    #[test]
+    fn test_word_types() {
+        let mut program: Intcode<i128> =
+            "1102,1125899906842624,1125899906842624,7,4,7,99,0".parse().unwrap();
+        itertools::assert_equal(program.run([]), [1 << 100]);
+
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut program: Intcode<i32> = quine.parse().unwrap();
+        itertools::assert_equal(program.run([]), quine.split(',').map(|e| e.parse().unwrap()));
+
+        let mut program: Intcode<i64> = "3,9,1002,9,3,9,4,9,99,0".parse().unwrap();
+        assert!(program.disassemble().to_string().contains("mul [9], 3, [9]"));
+        itertools::assert_equal(program.run([33]), [99]);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = Intcode::parse("3,3,1105,-1,9,1101,0,0,12,4,12,99,1");
+        let expect = r"
+    in [3]                   ; 0
+    jit -1, L9               ; 2
+    add 0, 0, [12]           ; 5
+L9:
+    out [12]                 ; 9
+    hlt                      ; 11
+    .data 1                  ; 12
+";
+        assert_eq!(program.disassemble().to_string(), expect.trim_start_matches('\n'));
+    }
+
+    #[test]
+    fn test_disassemble_data() {
+        // The jump lands in the middle of `add`, and 5 is a truncated instruction.
+        let program = Intcode::parse("1105,1,4,1,2,204,-3,0,5");
+        let items: Vec<_> = program.disassemble().lines.into_iter().map(|l| l.item).collect();
+        assert_eq!(
+            items,
+            [
+                Item::Code(Instruction::Jit(Param::Imme(1), Param::Imme(4))),
+                Item::Data(1),
+                Item::Code(Instruction::Mul(Param::Addr(204), Param::Addr(-3), Param::Addr(0))),
+                Item::Data(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_far_write() {
+        // Writes 7 to address 1 << 40.
+        let mut program = Intcode::parse("1101,3,4,1099511627776,99");
+        assert_eq!(program.run([]).count(), 0);
+        assert_eq!((program.code.len(), program.code.dense_len()), ((1 << 40) + 1, 5));
+        assert_eq!(program.disassemble().lines.len(), 2);
+        program.predecode();
+
+        let mut buf = vec![];
+        program.snapshot().save(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.ends_with("code 1101,3,4,1099511627776,99\nsparse 1099511627776=7\n"));
+        let loaded = Snapshot::load(text.as_bytes()).unwrap();
+        let restored = Intcode::from(&loaded);
+        assert_eq!((restored.code[1 << 40], restored.code.dense_len()), (7, 5));
+        assert_eq!(restored, program);
+        assert!(program.transpile().contains("set(&mut mem, 1099511627776, 7);"));
+    }
 }
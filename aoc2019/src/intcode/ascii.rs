@@ -0,0 +1,146 @@
+use super::{Deferred, Intcode};
+use std::cell::Cell;
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum Ascii {
+    /// A line of text, without the trailing newline.
+    Line(String),
+    /// An output that isn't an ASCII character, usually the answer.
+    Value(isize),
+}
+
+/// Talks to an Intcode program in ASCII mode: text goes in as bytes and comes out line by line.
+pub struct AsciiMachine<'a> {
+    deferred: Deferred<'a>,
+    /// A value read while a line was still pending, to be reported next.
+    held: Cell<Option<isize>>,
+}
+
+impl<'a> From<Deferred<'a>> for AsciiMachine<'a> {
+    fn from(deferred: Deferred<'a>) -> Self {
+        Self { deferred, held: Cell::new(None) }
+    }
+}
+
+impl<'a> AsciiMachine<'a> {
+    pub fn new(program: &'a mut Intcode) -> Self {
+        program.deferred_run().into()
+    }
+
+    pub fn send(&self, text: &str) {
+        self.deferred.send_seq(text.bytes().map(isize::from));
+    }
+
+    pub fn send_line(&self, line: &str) {
+        self.send(line);
+        self.deferred.send(b'\n'.into());
+    }
+
+    /// The next line or value. A line cut short by a value or by the program halting is still
+    /// reported, before the value.
+    pub fn next_event(&self) -> Option<Ascii> {
+        let mut output = self.deferred.iter();
+        let mut line = String::new();
+        loop {
+            let Some(x) = self.held.take().or_else(|| output.next()) else {
+                return (!line.is_empty()).then_some(Ascii::Line(line));
+            };
+            match u8::try_from(x) {
+                Ok(b'\n') => return Some(Ascii::Line(line)),
+                Ok(b) if b.is_ascii() => line.push(b.into()),
+                _ if !line.is_empty() => {
+                    self.held.set(Some(x));
+                    return Some(Ascii::Line(line));
+                }
+                _ => return Some(Ascii::Value(x)),
+            }
+        }
+    }
+
+    pub fn events<'b>(&'b self) -> impl Iterator<Item = Ascii> + 'b + use<'b, 'a> {
+        std::iter::from_fn(|| self.next_event())
+    }
+
+    /// Text lines up to the next value, which is left for [`AsciiMachine::next_event`].
+    pub fn lines<'b>(&'b self) -> impl Iterator<Item = String> + 'b + use<'b, 'a> {
+        std::iter::from_fn(|| match self.next_event()? {
+            Ascii::Line(line) => Some(line),
+            Ascii::Value(x) => {
+                self.held.set(Some(x));
+                None
+            }
+        })
+    }
+
+    /// Reads lines up to a line equal to `prompt`, and returns the lines before it. If the
+    /// program halts or waits for input first, returns everything it said as an error instead.
+    pub fn read_until_prompt(&self, prompt: &str) -> Result<Vec<String>, Vec<String>> {
+        let mut lines = vec![];
+        for line in self.lines() {
+            if line == prompt {
+                return Ok(lines);
+            }
+            lines.push(line);
+        }
+        Err(lines)
+    }
+}
+
+impl Intcode {
+    pub fn ascii_run(&mut self) -> AsciiMachine<'_> {
+        AsciiMachine::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Greets, echoes one line, then reports a value in the middle of a line.
+    const ECHO: &str = r"
+        out 72
+        out 105
+        out 10
+        out 63
+        out 10
+    loop:
+        in [c]
+        out [c]
+        eq [c], 10, [t]
+        jif [t], loop
+        out 33
+        out 1000
+        out 10
+        hlt
+    c:  .data 0
+    t:  .data 0
+    ";
+
+    #[test]
+    fn test_ascii() {
+        let mut program = Intcode::assemble(ECHO).unwrap();
+        let machine = program.ascii_run();
+        assert_eq!(machine.read_until_prompt("?"), Ok(vec!["Hi".to_string()]));
+        machine.send_line("abc");
+        assert_eq!(machine.lines().collect::<Vec<_>>(), ["abc", "!"]);
+        assert_eq!(machine.next_event(), Some(Ascii::Value(1000)));
+        assert_eq!(machine.events().collect::<Vec<_>>(), [Ascii::Line(String::new())]);
+    }
+
+    #[test]
+    fn test_no_prompt() {
+        let mut program = Intcode::parse("104,65,104,10,104,66,99");
+        let machine = program.ascii_run();
+        assert_eq!(machine.read_until_prompt("?"), Err(vec!["A".to_string(), "B".to_string()]));
+    }
+
+    #[test]
+    fn test_waits_for_input() {
+        // Says hello only after reading a byte.
+        let mut program = Intcode::parse("104,62,104,10,3,0,104,63,104,10,99");
+        let machine = program.ascii_run();
+        assert_eq!(machine.read_until_prompt("?"), Err(vec![">".to_string()]));
+        machine.send("x");
+        assert_eq!(machine.read_until_prompt("?"), Ok(vec![]));
+    }
+}
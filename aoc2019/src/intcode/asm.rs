@@ -0,0 +1,220 @@
+use super::{Instruction, Intcode, Param, Word};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+type Labels<'a> = HashMap<&'a str, usize>;
+
+fn arity(mnemonic: &str) -> Option<usize> {
+    match mnemonic {
+        "add" | "mul" | "lt" | "eq" => Some(3),
+        "jit" | "jif" => Some(2),
+        "in" | "out" | "arb" => Some(1),
+        "hlt" => Some(0),
+        _ => None,
+    }
+}
+
+pub(super) fn build<W: Word>(mnemonic: &str, params: &[Param<W>]) -> Option<Instruction<W>> {
+    Some(match (mnemonic, params) {
+        ("add", &[a, b, c]) => Instruction::Add(a, b, c),
+        ("mul", &[a, b, c]) => Instruction::Mul(a, b, c),
+        ("in", &[a]) => Instruction::Input(a),
+        ("out", &[a]) => Instruction::Output(a),
+        ("jit", &[a, b]) => Instruction::Jit(a, b),
+        ("jif", &[a, b]) => Instruction::Jif(a, b),
+        ("lt", &[a, b, c]) => Instruction::Tlt(a, b, c),
+        ("eq", &[a, b, c]) => Instruction::Teq(a, b, c),
+        ("arb", &[a]) => Instruction::Rela(a),
+        ("hlt", &[]) => Instruction::Halt,
+        _ => return None,
+    })
+}
+
+fn is_ident(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Sum of integer literals and labels, e.g. `-1`, `loop`, `buf+3`.
+fn eval<W: Word>(expr: &str, labels: &Labels) -> Option<W> {
+    if expr.is_empty() {
+        return None;
+    }
+    let label = |t| W::try_from(*labels.get(t)?).ok();
+    let expr = expr.replace('-', "+-");
+    expr.split('+')
+        .enumerate()
+        .filter(|&(i, t)| i > 0 || !t.is_empty())
+        .map(|(_, t)| {
+            t.parse().ok().or_else(|| match t.strip_prefix('-') {
+                Some(t) => label(t).map(|a| -a),
+                None => label(t),
+            })
+        })
+        .try_fold(W::ZERO, |sum, t| sum.checked_add(t?))
+}
+
+/// `expr` is immediate, `[expr]` is positional and `[ra+expr]` is relative.
+fn operand<W: Word>(s: &str, labels: &Labels) -> Option<Param<W>> {
+    let s: String = s.split_whitespace().collect();
+    let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) else {
+        return Some(Param::Imme(eval(&s, labels)?));
+    };
+    match inner.strip_prefix("ra") {
+        Some("") => Some(Param::Rela(W::ZERO)),
+        Some(rest) if rest.starts_with(['+', '-']) => Some(Param::Rela(eval(rest, labels)?)),
+        _ => Some(Param::Addr(eval(inner, labels)?)),
+    }
+}
+
+/// Assembles the listing syntax produced by [`Intcode::disassemble`].
+///
+/// Each line holds an optional `label:`, then either an instruction (`add [9], 3, [ra-1]`) or a
+/// `.data` directive with comma separated values. Everything after a `;` is a comment.
+pub fn assemble<W: Word>(src: &str) -> Result<Vec<W>, AsmError> {
+    let mut labels = Labels::new();
+    let mut stmts = vec![];
+    let mut addr = 0;
+    for (line, text) in src.lines().enumerate().map(|(i, t)| (i + 1, t)) {
+        let err = |msg| AsmError { line, msg };
+        let mut text = text.split(';').next().unwrap().trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_ident(label) || label == "ra" {
+                return Err(err(format!("invalid label `{label}`")));
+            }
+            if labels.insert(label, addr).is_some() {
+                return Err(err(format!("duplicate label `{label}`")));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (mnemonic, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let args: Vec<&str> = match args.trim() {
+            "" => vec![],
+            args => args.split(',').map(str::trim).collect(),
+        };
+        addr += match mnemonic {
+            ".data" => args.len(),
+            _ => {
+                1 + arity(mnemonic).ok_or_else(|| err(format!("unknown mnemonic `{mnemonic}`")))?
+            }
+        };
+        stmts.push((line, mnemonic, args));
+    }
+
+    let mut code = Vec::with_capacity(addr);
+    for (line, mnemonic, args) in stmts {
+        let err = |msg| AsmError { line, msg };
+        if mnemonic == ".data" {
+            for arg in args {
+                let expr: String = arg.split_whitespace().collect();
+                let value =
+                    eval(&expr, &labels).ok_or_else(|| err(format!("invalid value `{arg}`")))?;
+                code.push(value);
+            }
+            continue;
+        }
+        let params = args
+            .iter()
+            .map(|arg| operand(arg, &labels).ok_or_else(|| err(format!("invalid operand `{arg}`"))))
+            .collect::<Result<Vec<Param<W>>, _>>()?;
+        let instruction = build(mnemonic, &params).ok_or_else(|| {
+            err(format!("`{mnemonic}` expects {} operands", arity(mnemonic).unwrap()))
+        })?;
+        if let Some(p @ Param::Imme(_)) = instruction.dest() {
+            return Err(err(format!("cannot write to immediate operand `{p}`")));
+        }
+        code.extend(instruction.encode());
+    }
+    Ok(code)
+}
+
+impl Intcode {
+    pub fn assemble(src: &str) -> Result<Self, AsmError> {
+        Ok(Self { code: assemble(src)?.into(), ..Default::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_intcode;
+    use itertools::Itertools;
+
+    const PROGRAMS: &[&str] = &[
+        "3,9,1002,9,3,9,4,9,99,0",
+        "3,9,8,9,10,9,4,9,99,-1,8",
+        "3,3,1108,-1,8,3,4,3,99",
+        "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+        "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+        "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,\
+         20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+        "1102,34915192,34915192,7,4,7,99,0",
+        "104,1125899906842624,99",
+        "10099,1,2,3,22201,-1,1,0,1101,1,1,1",
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        for program in PROGRAMS {
+            let program = Intcode::parse(program);
+            let listing = program.disassemble().to_string();
+            assert_eq!(assemble(&listing), Ok(program.code.to_vec()), "{listing}");
+        }
+    }
+
+    #[test]
+    fn test_countdown() {
+        let src = r"
+            in [n]
+        loop:
+            out [n]
+            add [n], -1, [n]
+            jit [n], loop
+            hlt
+        n:  .data 0
+        ";
+        let program = assemble::<isize>(src).unwrap().iter().join(",");
+        assert_eq!(program, "3,12,4,12,1001,12,-1,12,1005,12,2,99,0");
+        assert_intcode!(&program, [3], [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_relative() {
+        let src = r"
+            arb stack+2
+            out [ra-1]          ; prints stack+1
+            out [ra]
+            hlt
+        stack: .data 7, 8, 9
+        ";
+        assert_eq!(Intcode::assemble(src).unwrap().run([]).collect_vec(), [8, 9]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let line = |src| assemble::<isize>(src).unwrap_err().line;
+        assert_eq!(line("hlt\nnop"), 2);
+        assert_eq!(line("add 1, 2"), 1);
+        assert_eq!(line("add 1, 2, 3"), 1);
+        assert_eq!(line("\n\njit 1, nowhere"), 3);
+        assert_eq!(line("a: hlt\na: hlt"), 2);
+    }
+}
@@ -0,0 +1,484 @@
+use super::asm::build;
+use super::{sweep, to_addr, Instruction, Intcode, Item, Param, Word};
+use itertools::Itertools;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Edge {
+    /// Falls through to the next block.
+    Next(usize),
+    /// A jump to a known address.
+    Taken(usize),
+    /// A jump to `target` after storing `ret` as the return address.
+    Call { target: usize, ret: usize },
+    /// A jump through an address read from the stack frame.
+    Return,
+    /// Any other jump to an address computed at run time.
+    Indirect,
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Block<W = isize> {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction<W>)>,
+    pub edges: Vec<Edge>,
+    /// Entry of the function the block belongs to.
+    pub function: usize,
+    /// Offset of ra when the block starts, relative to its value at function entry. Unknown if
+    /// the function adjusts ra by a computed amount or reaches the block with different offsets.
+    pub frame: Option<W>,
+}
+
+/// Control-flow graph of the code reachable from address 0. Words that are never reached are
+/// left out as data.
+#[derive(Debug, Clone, Default)]
+pub struct Cfg<W = isize> {
+    pub blocks: BTreeMap<usize, Block<W>>,
+    /// Address 0 and every call target.
+    pub functions: BTreeSet<usize>,
+    pub data: Vec<Range<usize>>,
+}
+
+/// How control leaves an instruction.
+enum Flow<W> {
+    Next,
+    Halt,
+    Jump { target: Param<W>, conditional: bool },
+}
+
+fn flow<W: Word>(instruction: &Instruction<W>) -> Flow<W> {
+    let (cond, target, if_nonzero) = match *instruction {
+        Instruction::Jit(a, b) => (a, b, true),
+        Instruction::Jif(a, b) => (a, b, false),
+        Instruction::Halt => return Flow::Halt,
+        _ => return Flow::Next,
+    };
+    match cond {
+        Param::Imme(c) if (c != W::ZERO) != if_nonzero => Flow::Next,
+        Param::Imme(_) => Flow::Jump { target, conditional: false },
+        _ => Flow::Jump { target, conditional: true },
+    }
+}
+
+fn static_target<W: Word>(target: Param<W>) -> Option<usize> {
+    match target {
+        Param::Imme(t) => to_addr(t).ok(),
+        _ => None,
+    }
+}
+
+/// The immediate stored by `add r, 0, dest` and the like, a candidate return address.
+fn stored_immediate<W: Word>(instruction: &Instruction<W>) -> Option<W> {
+    let (zero, one) = (W::ZERO, W::from(1));
+    match *instruction {
+        Instruction::Add(Param::Imme(r), Param::Imme(z), _)
+        | Instruction::Add(Param::Imme(z), Param::Imme(r), _)
+            if z == zero =>
+        {
+            Some(r)
+        }
+        Instruction::Mul(Param::Imme(r), Param::Imme(o), _)
+        | Instruction::Mul(Param::Imme(o), Param::Imme(r), _)
+            if o == one =>
+        {
+            Some(r)
+        }
+        _ => None,
+    }
+}
+
+/// Immediate operands that the program overwrites are really reads from memory.
+fn resolve<W: Word>(
+    addr: usize,
+    instruction: Instruction<W>,
+    written: &HashSet<usize>,
+) -> Instruction<W> {
+    let mut params = instruction.params();
+    for (i, p) in params.iter_mut().enumerate() {
+        if let (Param::Imme(_), true, Ok(a)) =
+            (*p, written.contains(&(addr + 1 + i)), W::try_from(addr + 1 + i))
+        {
+            *p = Param::Addr(a);
+        }
+    }
+    build(instruction.mnemonic(), &params).unwrap_or(instruction)
+}
+
+impl<W: Word> Intcode<W> {
+    pub fn cfg(&self) -> Cfg<W> {
+        Cfg::new(&self.code.to_vec())
+    }
+}
+
+impl<W: Word> Cfg<W> {
+    pub fn new(code: &[W]) -> Self {
+        let written: HashSet<usize> = sweep(code, |_| false)
+            .filter_map(|(_, item)| match item {
+                Item::Code(i) => match i.dest()? {
+                    Param::Addr(a) => to_addr(a).ok(),
+                    _ => None,
+                },
+                Item::Data(_) => None,
+            })
+            .collect();
+
+        // Follow every path from 0 to find the instructions, leaders and calls.
+        let mut instructions = BTreeMap::new();
+        let mut leaders = BTreeSet::from([0]);
+        let mut calls = HashMap::new();
+        let mut functions = BTreeSet::from([0]);
+        let mut work = vec![0];
+        while let Some(mut addr) = work.pop() {
+            let mut stored = HashSet::new();
+            while !instructions.contains_key(&addr) {
+                let Some(instruction) = code.get(addr..).and_then(Instruction::decode) else {
+                    break;
+                };
+                let instruction = resolve(addr, instruction, &written);
+                instructions.insert(addr, instruction);
+                let next = addr + instruction.width();
+                stored.extend(stored_immediate(&instruction));
+                match flow(&instruction) {
+                    Flow::Next => {}
+                    Flow::Halt => break,
+                    Flow::Jump { target, conditional } => {
+                        leaders.insert(next);
+                        if let Some(t) = static_target(target) {
+                            leaders.insert(t);
+                            work.push(t);
+                            if !conditional && W::try_from(next).is_ok_and(|r| stored.contains(&r))
+                            {
+                                calls.insert(addr, (t, next));
+                                functions.insert(t);
+                                work.push(next);
+                            }
+                        }
+                        if !conditional {
+                            break;
+                        }
+                    }
+                }
+                addr = next;
+            }
+        }
+
+        // Split the instructions into blocks.
+        let falls_into: HashSet<usize> = instructions
+            .iter()
+            .filter(|(_, i)| matches!(flow(*i), Flow::Next | Flow::Jump { conditional: true, .. }))
+            .map(|(&a, i)| a + i.width())
+            .collect();
+        let mut blocks = BTreeMap::new();
+        for &start in instructions.keys() {
+            if !leaders.contains(&start) && falls_into.contains(&start) {
+                continue;
+            }
+            let mut block =
+                Block { start, instructions: vec![], edges: vec![], function: 0, frame: None };
+            let mut addr = start;
+            while let Some(&instruction) = instructions.get(&addr) {
+                block.instructions.push((addr, instruction));
+                let next = addr + instruction.width();
+                let fall = instructions.contains_key(&next).then_some(Edge::Next(next));
+                match flow(&instruction) {
+                    Flow::Next if leaders.contains(&next) => block.edges.extend(fall),
+                    Flow::Next => {
+                        addr = next;
+                        continue;
+                    }
+                    Flow::Halt => {}
+                    Flow::Jump { target, conditional } => {
+                        block.edges.push(match (static_target(target), calls.get(&addr)) {
+                            (_, Some(&(target, ret))) => Edge::Call { target, ret },
+                            (Some(t), None) => Edge::Taken(t),
+                            (None, None) if matches!(target, Param::Rela(_)) => Edge::Return,
+                            (None, None) => Edge::Indirect,
+                        });
+                        if conditional {
+                            block.edges.extend(fall);
+                        }
+                    }
+                }
+                break;
+            }
+            blocks.insert(start, block);
+        }
+
+        let mut cfg = Self { blocks, functions, data: vec![] };
+        cfg.assign_frames();
+        let mut covered = vec![false; code.len()];
+        for (&addr, instruction) in &instructions {
+            covered[addr..(addr + instruction.width()).min(code.len())].fill(true);
+        }
+        cfg.data = covered
+            .iter()
+            .enumerate()
+            .chunk_by(|(_, &c)| c)
+            .into_iter()
+            .filter(|(c, _)| !c)
+            .map(|(_, mut run)| {
+                let start = run.next().unwrap().0;
+                start..run.last().map_or(start, |(a, _)| a) + 1
+            })
+            .collect();
+        cfg
+    }
+
+    /// Walks each function from its entry, tracking how `arb` moves ra from its entry value.
+    /// Calls are assumed to leave ra as they found it.
+    fn assign_frames(&mut self) {
+        let mut assigned = HashSet::new();
+        for &entry in &self.functions {
+            let mut frames: HashMap<usize, Option<W>> = HashMap::from([(entry, Some(W::ZERO))]);
+            let mut work = vec![entry];
+            while let Some(start) = work.pop() {
+                let Some(block) = self.blocks.get_mut(&start) else { continue };
+                if assigned.insert(start) {
+                    block.function = entry;
+                }
+                let frame = frames[&start];
+                if block.function == entry {
+                    block.frame = frame;
+                }
+                let out = block.instructions.iter().try_fold(frame, |f, (_, i)| match i {
+                    Instruction::Rela(Param::Imme(k)) => Some(f.and_then(|f| f.checked_add(*k))),
+                    Instruction::Rela(_) => None,
+                    _ => Some(f),
+                });
+                let out = out.flatten();
+                for edge in &block.edges {
+                    let (Edge::Next(succ) | Edge::Taken(succ) | Edge::Call { ret: succ, .. }) =
+                        *edge
+                    else {
+                        continue;
+                    };
+                    let merged = match frames.get(&succ) {
+                        None => out,
+                        Some(&f) if f == out => continue,
+                        Some(_) => None,
+                    };
+                    if frames.insert(succ, merged) != Some(merged) {
+                        work.push(succ);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Exports the graph in Graphviz DOT format, one node per block.
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let text: String = block.instructions.iter().map(|(_, i)| format!("{i}\\l")).collect();
+            writeln!(dot, "    L{} [label=\"L{}:\\l{text}\"];", block.start, block.start).unwrap();
+            for edge in &block.edges {
+                let (succ, attrs) = match *edge {
+                    Edge::Next(succ) => (succ, ""),
+                    Edge::Taken(succ) => (succ, " [label=\"taken\"]"),
+                    Edge::Call { target, ret } => {
+                        writeln!(
+                            dot,
+                            "    L{} -> L{target} [label=\"call\", style=dashed];",
+                            block.start
+                        )
+                        .unwrap();
+                        (ret, " [label=\"return\", style=dotted]")
+                    }
+                    Edge::Return | Edge::Indirect => continue,
+                };
+                writeln!(dot, "    L{} -> L{succ}{attrs};", block.start).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the code as pseudo-C, one function per call target. `frame[k]` is memory at ra+k,
+    /// with ra as it was when the function was entered.
+    pub fn decompile(&self) -> String {
+        let returns: HashSet<usize> = self
+            .blocks
+            .values()
+            .flat_map(|b| &b.edges)
+            .filter_map(|e| match *e {
+                Edge::Call { ret, .. } => Some(ret),
+                _ => None,
+            })
+            .collect();
+        let mut out = String::new();
+        for &entry in &self.functions {
+            writeln!(out, "void f{entry}(void) {{").unwrap();
+            let blocks: Vec<_> = self.blocks.values().filter(|b| b.function == entry).collect();
+            for (n, block) in blocks.iter().enumerate() {
+                writeln!(out, "L{}:", block.start).unwrap();
+                let mut frame = block.frame;
+                for &(addr, instruction) in &block.instructions {
+                    let is_last = addr == block.instructions.last().unwrap().0;
+                    let stmt = statement(&instruction, frame, &returns, is_last.then_some(block));
+                    writeln!(out, "    {stmt}").unwrap();
+                    if let Instruction::Rela(p) = instruction {
+                        frame = match p {
+                            Param::Imme(k) => frame.and_then(|f| f.checked_add(k)),
+                            _ => None,
+                        };
+                    }
+                }
+                let fall = block.edges.iter().find_map(|e| match *e {
+                    Edge::Next(succ) | Edge::Call { ret: succ, .. } => Some(succ),
+                    _ => None,
+                });
+                if let Some(succ) = fall.filter(|&s| blocks.get(n + 1).is_none_or(|b| b.start != s))
+                {
+                    writeln!(out, "    goto L{succ};").unwrap();
+                }
+            }
+            writeln!(out, "}}\n").unwrap();
+        }
+        for range in &self.data {
+            writeln!(out, "// data: {}..{}", range.start, range.end).unwrap();
+        }
+        out
+    }
+}
+
+/// `block` is set for the instruction that ends a block, so jumps can use its edges.
+fn statement<W: Word>(
+    instruction: &Instruction<W>,
+    frame: Option<W>,
+    returns: &HashSet<usize>,
+    block: Option<&Block<W>>,
+) -> String {
+    let p = |p: Param<W>| match p {
+        Param::Imme(v) => v.to_string(),
+        Param::Addr(a) => format!("mem[{a}]"),
+        Param::Rela(k) => match frame.and_then(|f| f.checked_add(k)) {
+            Some(k) => format!("frame[{k}]"),
+            None if k >= W::ZERO => format!("mem[ra+{k}]"),
+            None => format!("mem[ra{k}]"),
+        },
+    };
+    match *instruction {
+        Instruction::Add(a, b, c) => {
+            match stored_immediate(instruction).and_then(|r| to_addr(r).ok()) {
+                Some(r) if returns.contains(&r) => format!("{} = &&L{r};", p(c)),
+                _ => format!("{} = {} + {};", p(c), p(a), p(b)),
+            }
+        }
+        Instruction::Mul(a, b, c) => format!("{} = {} * {};", p(c), p(a), p(b)),
+        Instruction::Tlt(a, b, c) => format!("{} = {} < {};", p(c), p(a), p(b)),
+        Instruction::Teq(a, b, c) => format!("{} = {} == {};", p(c), p(a), p(b)),
+        Instruction::Input(a) => format!("{} = input();", p(a)),
+        Instruction::Output(a) => format!("output({});", p(a)),
+        Instruction::Rela(a) => format!("ra += {};", p(a)),
+        Instruction::Halt => "halt();".to_string(),
+        Instruction::Jit(a, b) | Instruction::Jif(a, b) => {
+            let Flow::Jump { target, conditional } = flow(instruction) else {
+                return format!("; // never taken: {instruction}");
+            };
+            let cond = match instruction {
+                Instruction::Jit(..) => p(a),
+                _ => format!("!{}", p(a)),
+            };
+            let edge = block.and_then(|b| b.edges.first());
+            let jump = match (edge, target) {
+                (Some(Edge::Call { target, .. }), _) => format!("f{target}();"),
+                (Some(Edge::Return), _) => format!("return; // to {}", p(b)),
+                (_, Param::Imme(t)) => format!("goto L{t};"),
+                _ => format!("goto *{};", p(b)),
+            };
+            match conditional {
+                true => format!("if ({cond}) {jump}"),
+                false => jump,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Doubles its input through a function that keeps its argument on the stack.
+    const CALL: &str = r"
+        arb stack
+        in [ra+1]
+        add ret, 0, [ra]
+        jit 1, double
+    ret:
+        out [ra+1]
+        hlt
+    double:
+        arb 2
+        mul [ra-1], 2, [ra-1]
+        arb -2
+        jit 1, [ra]
+    stack: .data 0
+    ";
+
+    #[test]
+    fn test_blocks() {
+        let cfg = Intcode::parse("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").cfg();
+        let edges: Vec<_> = cfg.blocks.values().map(|b| (b.start, b.edges.clone())).collect();
+        assert_eq!(
+            edges,
+            [(0, vec![Edge::Taken(9), Edge::Next(5)]), (5, vec![Edge::Next(9)]), (9, vec![])]
+        );
+        assert_eq!(cfg.data.len(), 1);
+        assert_eq!(cfg.data[0], 12..13);
+        let expect = r#"
+digraph cfg {
+    node [shape=box, fontname="monospace"];
+    L0 [label="L0:\lin [3]\ljit [3], 9\l"];
+    L0 -> L9 [label="taken"];
+    L0 -> L5;
+    L5 [label="L5:\ladd 0, 0, [12]\l"];
+    L5 -> L9;
+    L9 [label="L9:\lout [12]\lhlt\l"];
+}
+"#;
+        assert_eq!(cfg.to_dot(), expect.trim_start_matches('\n'));
+    }
+
+    #[test]
+    fn test_decompile() {
+        let program = Intcode::assemble(CALL).unwrap();
+        assert_eq!(program.clone().run([21]).collect::<Vec<_>>(), [42]);
+        let cfg = program.cfg();
+        assert_eq!(cfg.functions, BTreeSet::from([0, 14]));
+        let expect = r"
+void f0(void) {
+L0:
+    ra += 25;
+    frame[26] = input();
+    frame[25] = &&L11;
+    f14();
+L11:
+    output(frame[26]);
+    halt();
+}
+
+void f14(void) {
+L14:
+    ra += 2;
+    frame[1] = frame[1] * 2;
+    ra += -2;
+    return; // to frame[0]
+}
+
+// data: 25..26
+";
+        assert_eq!(cfg.decompile(), expect.trim_start_matches('\n'));
+    }
+
+    #[test]
+    fn test_data_between_code() {
+        // Jumps over a word that isn't an instruction, and a truncated one at the end.
+        let cfg = Intcode::parse("1105,1,4,7,104,1,99,1,2").cfg();
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), [0, 4]);
+        assert_eq!(cfg.data, [3..4, 7..9]);
+        assert_eq!(cfg.blocks[&4].frame, Some(0));
+    }
+}
@@ -0,0 +1,150 @@
+use super::{ErrorKind, Event, Intcode, Memory, Word};
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// Guards against programs that never stop. These are settings rather than machine state, so
+/// they don't take part in comparisons.
+#[derive(Default, Clone)]
+pub(super) struct Limits<W: Word> {
+    /// Instructions left to execute.
+    budget: Option<usize>,
+    /// The states seen at backward jumps since the last input or output.
+    seen: Option<HashSet<(usize, W, Memory<W>)>>,
+}
+
+impl<W: Word> PartialEq for Limits<W> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<W: Word> Eq for Limits<W> {}
+
+impl<W: Word> Hash for Limits<W> {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+impl<W: Word> std::fmt::Debug for Limits<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Limits")
+            .field("budget", &self.budget)
+            .field("seen", &self.seen.as_ref().map(HashSet::len))
+            .finish()
+    }
+}
+
+impl<W: Word> Intcode<W> {
+    /// Limits the number of instructions executed from now on. Once they are used up, stepping
+    /// fails with [`ErrorKind::BudgetExhausted`] and leaves the machine untouched, so it can
+    /// resume after the budget is raised again.
+    pub fn set_step_budget(&mut self, steps: Option<usize>) {
+        self.limits.budget = steps;
+    }
+
+    pub fn step_budget(&self) -> Option<usize> {
+        self.limits.budget
+    }
+
+    /// Gives a bounded machine `steps` more instructions.
+    pub fn add_steps(&mut self, steps: usize) {
+        if let Some(budget) = self.limits.budget.as_mut() {
+            *budget += steps;
+        }
+    }
+
+    /// Makes stepping fail with [`ErrorKind::InfiniteLoop`] when the machine jumps backwards
+    /// into a state it was in before, without any input or output in between. This keeps a copy
+    /// of the machine at every backward jump. The copies share unchanged memory pages, but
+    /// hashing and comparing them makes it slow.
+    pub fn set_loop_detection(&mut self, enabled: bool) {
+        self.limits.seen = enabled.then(Default::default);
+    }
+
+    pub(super) fn check_budget(&self) -> Result<(), ErrorKind<W>> {
+        match self.limits.budget {
+            Some(0) => Err(ErrorKind::BudgetExhausted),
+            _ => Ok(()),
+        }
+    }
+
+    /// Accounts for the instruction just executed at `pc`, which left `pending` inputs.
+    pub(super) fn check_limits(
+        &mut self,
+        pc: usize,
+        pending: usize,
+        event: &Event<W>,
+    ) -> Result<(), ErrorKind<W>> {
+        if matches!(event, Event::NeedsInput | Event::Halted) {
+            return Ok(());
+        }
+        if let Some(budget) = self.limits.budget.as_mut() {
+            *budget -= 1;
+        }
+        let Some(seen) = self.limits.seen.as_mut() else {
+            return Ok(());
+        };
+        if matches!(event, Event::Output(_)) || pending != self.input.len() {
+            seen.clear();
+        } else if self.pc <= pc && !seen.insert((self.pc, self.ra, self.code.clone())) {
+            return Err(ErrorKind::InfiniteLoop);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    // Counts down from the input, printing each number.
+    const COUNTDOWN: &str = r"
+        in [n]
+    loop:
+        out [n]
+        add [n], -1, [n]
+        jit [n], loop
+        hlt
+    n:  .data 0
+    ";
+
+    #[test]
+    fn test_budget() {
+        let mut program = Intcode::assemble(COUNTDOWN).unwrap();
+        program.push_input(3);
+        program.set_step_budget(Some(7));
+        let outputs: Vec<_> = program.try_run([]).collect();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs[..2], [Ok(3), Ok(2)]);
+        let e = outputs[2].unwrap_err();
+        assert_eq!((e.pc, e.kind), (2, ErrorKind::BudgetExhausted));
+        assert_eq!(e.to_string(), "step budget exhausted at pc=2");
+
+        program.add_steps(100);
+        assert_eq!(program.run([]).collect::<Vec<_>>(), [1]);
+        assert_eq!(program.step_budget(), Some(100 - 3));
+    }
+
+    #[test]
+    fn test_loop_detection() {
+        let mut program = Intcode::assemble(COUNTDOWN).unwrap();
+        program.set_loop_detection(true);
+        assert_eq!(program.run([3]).collect::<Vec<_>>(), [3, 2, 1]);
+
+        // Waits for the first input to be non-zero, and never reads another one.
+        let mut program = Intcode::assemble(
+            r"
+            in [x]
+        wait:
+            jif [x], wait
+            out [x]
+            hlt
+        x:  .data 0
+        ",
+        )
+        .unwrap();
+        program.set_loop_detection(true);
+        let e = program.try_run([0]).last().unwrap().unwrap_err();
+        assert_eq!((e.pc, e.kind), (2, ErrorKind::InfiniteLoop));
+        assert_eq!(e.to_string(), "infinite loop at pc=2");
+    }
+}
@@ -0,0 +1,348 @@
+use super::{ErrorKind, Instruction, Word};
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+const PAGE_SIZE: usize = 1024;
+const DECODED_PAGE_SIZE: usize = 64;
+
+type Page<W> = [W; PAGE_SIZE];
+type DecodedPage<W> = [Option<Instruction<W>>; DECODED_PAGE_SIZE];
+
+/// Instructions decoded from memory, keyed by address. Like memory, pages are copy-on-write.
+#[derive(Clone)]
+struct DecodeCache<W> {
+    enabled: bool,
+    pages: Vec<Arc<DecodedPage<W>>>,
+}
+
+impl<W: Word> DecodeCache<W> {
+    fn get(&self, idx: usize) -> Option<Instruction<W>> {
+        self.pages.get(idx / DECODED_PAGE_SIZE)?[idx % DECODED_PAGE_SIZE]
+    }
+
+    fn insert(&mut self, idx: usize, instruction: Instruction<W>) {
+        let p = idx / DECODED_PAGE_SIZE;
+        if p >= self.pages.len() {
+            self.pages.resize(p + 1, Arc::new([None; DECODED_PAGE_SIZE]));
+        }
+        Arc::make_mut(&mut self.pages[p])[idx % DECODED_PAGE_SIZE] = Some(instruction);
+    }
+
+    /// Drops every cached instruction that covers `idx`.
+    fn invalidate(&mut self, idx: usize) {
+        for a in idx.saturating_sub(3)..=idx {
+            if let Some(page) = self.pages.get_mut(a / DECODED_PAGE_SIZE) {
+                if page[a % DECODED_PAGE_SIZE].is_some_and(|i| a + i.width() > idx) {
+                    Arc::make_mut(page)[a % DECODED_PAGE_SIZE] = None;
+                }
+            }
+        }
+    }
+}
+
+impl<W> Default for DecodeCache<W> {
+    fn default() -> Self {
+        Self { enabled: true, pages: vec![] }
+    }
+}
+
+// The cache is derived from memory and must not affect comparisons.
+impl<W> PartialEq for DecodeCache<W> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<W> Eq for DecodeCache<W> {}
+
+impl<W> Hash for DecodeCache<W> {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+impl<W> std::fmt::Debug for DecodeCache<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecodeCache").field("enabled", &self.enabled).finish_non_exhaustive()
+    }
+}
+
+/// Zero-extended Intcode memory. Pages from the start of memory up to the first gap are stored
+/// densely, pages past it are only allocated once written. Pages are copy-on-write, so a clone
+/// only copies the pages that either side writes to afterwards.
+///
+/// The dense part holds the program and whatever it writes next to it. Anything that walks the
+/// program, like [`Memory::iter`], stays in the dense part, so a single write far away costs one
+/// page rather than everything up to it.
+#[derive(Debug, Default, Clone)]
+pub struct Memory<W: Word = isize> {
+    dense: Vec<Arc<Page<W>>>,
+    sparse: BTreeMap<usize, Arc<Page<W>>>,
+    /// One past the highest address written.
+    len: usize,
+    /// One past the highest address written in the dense pages.
+    dense_len: usize,
+    cache: DecodeCache<W>,
+    /// What unallocated addresses read as.
+    zero: W,
+}
+
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub struct MemoryStats {
+    pub dense_pages: usize,
+    pub sparse_pages: usize,
+    /// Pages shared with another clone of this memory.
+    pub shared_pages: usize,
+    /// Bytes allocated for pages, counting shared pages in full.
+    pub bytes: usize,
+}
+
+impl<W: Word> Memory<W> {
+    /// One past the highest address written, sparse pages included.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// One past the highest address written in the dense part.
+    pub fn dense_len(&self) -> usize {
+        self.dense_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, idx: usize) -> W {
+        self[idx]
+    }
+
+    /// The `N` words from `idx` on. Addresses past `usize::MAX` read as zero.
+    pub fn read<const N: usize>(&self, idx: usize) -> [W; N] {
+        std::array::from_fn(|i| idx.checked_add(i).map_or(self.zero, |a| self[a]))
+    }
+
+    /// Every word of the dense part, up to [`Memory::dense_len`].
+    pub fn iter(&self) -> impl Iterator<Item = W> + '_ {
+        (0..self.dense_len).map(|idx| self[idx])
+    }
+
+    pub fn to_vec(&self) -> Vec<W> {
+        self.iter().collect()
+    }
+
+    /// The nonzero words outside the dense part, by address.
+    pub fn sparse(&self) -> impl Iterator<Item = (usize, W)> + '_ {
+        self.sparse.iter().flat_map(|(&p, page)| {
+            let words = page.iter().enumerate().filter(|(_, &x)| x != W::ZERO);
+            words.map(move |(i, &x)| (p * PAGE_SIZE + i, x))
+        })
+    }
+
+    /// Decodes the instruction at `idx`, reusing the previous result until memory under it is
+    /// written.
+    pub fn decode(&mut self, idx: usize) -> Result<Instruction<W>, ErrorKind<W>> {
+        if let Some(instruction) = self.cache.get(idx) {
+            return Ok(instruction);
+        }
+        let instruction = Instruction::parse(&self.read::<4>(idx))?;
+        // Code outside the dense pages is rare, and caching it would need a sparse cache too.
+        if self.cache.enabled && idx < self.dense.len() * PAGE_SIZE {
+            self.cache.insert(idx, instruction);
+        }
+        Ok(instruction)
+    }
+
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache = DecodeCache { enabled, pages: vec![] };
+    }
+
+    fn page_mut(&mut self, p: usize) -> &mut Page<W> {
+        let zero = || Arc::new([W::ZERO; PAGE_SIZE]);
+        if p == self.dense.len() {
+            // Keep the dense part contiguous, absorbing any sparse pages that now follow it.
+            self.dense.push(self.sparse.remove(&p).unwrap_or_else(zero));
+            while let Some(page) = self.sparse.remove(&self.dense.len()) {
+                if let Some(i) = page.iter().rposition(|&x| x != W::ZERO) {
+                    self.dense_len = self.dense_len.max(self.dense.len() * PAGE_SIZE + i + 1);
+                }
+                self.dense.push(page);
+            }
+        }
+        let page = match self.dense.get_mut(p) {
+            Some(page) => page,
+            None => self.sparse.entry(p).or_insert_with(zero),
+        };
+        Arc::make_mut(page)
+    }
+
+    /// Number of pages shared with another clone of this memory.
+    pub fn shared_pages(&self) -> usize {
+        self.pages().filter(|(_, p)| Arc::strong_count(p) > 1).count()
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let (dense_pages, sparse_pages) = (self.dense.len(), self.sparse.len());
+        MemoryStats {
+            dense_pages,
+            sparse_pages,
+            shared_pages: self.shared_pages(),
+            bytes: (dense_pages + sparse_pages) * std::mem::size_of::<Page<W>>(),
+        }
+    }
+}
+
+impl<W: Word> Memory<W> {
+    fn pages(&self) -> impl Iterator<Item = (usize, &Arc<Page<W>>)> {
+        self.dense.iter().enumerate().chain(self.sparse.iter().map(|(&p, page)| (p, page)))
+    }
+
+    fn page(&self, p: usize) -> Option<&Arc<Page<W>>> {
+        self.dense.get(p).or_else(|| self.sparse.get(&p))
+    }
+}
+
+fn is_zero<W: Word>(page: &Page<W>) -> bool {
+    page.iter().all(|x| *x == W::default())
+}
+
+// Memories compare by content, however their pages happen to be laid out or how far they were
+// written with zeros.
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Self) -> bool {
+        let covers = |a: &Self, b: &Self| {
+            a.pages().all(|(p, page)| b.page(p).map_or_else(|| is_zero(page), |q| q == page))
+        };
+        covers(self, other) && covers(other, self)
+    }
+}
+
+impl<W: Word> Eq for Memory<W> {}
+
+impl<W: Word> Hash for Memory<W> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (p, page) in self.pages().filter(|(_, page)| !is_zero(page)) {
+            p.hash(state);
+            page.hash(state);
+        }
+    }
+}
+
+impl<W: Word> From<&[W]> for Memory<W> {
+    fn from(code: &[W]) -> Self {
+        let dense = code
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [W::ZERO; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+        Self { dense, len: code.len(), dense_len: code.len(), ..Default::default() }
+    }
+}
+
+impl<W: Word> From<Vec<W>> for Memory<W> {
+    fn from(code: Vec<W>) -> Self {
+        Self::from(code.as_slice())
+    }
+}
+
+impl<W: Word> FromIterator<W> for Memory<W> {
+    fn from_iter<I: IntoIterator<Item = W>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<W: Word> Index<usize> for Memory<W> {
+    type Output = W;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        self.page(idx / PAGE_SIZE).map_or(&self.zero, |p| &p[idx % PAGE_SIZE])
+    }
+}
+
+/// Writing past the end grows the memory, like the puzzle requires. Only the written page is
+/// allocated.
+impl<W: Word> IndexMut<usize> for Memory<W> {
+    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        self.len = self.len.max(idx.saturating_add(1));
+        if idx / PAGE_SIZE <= self.dense.len() {
+            self.dense_len = self.dense_len.max(idx + 1);
+        }
+        self.cache.invalidate(idx);
+        &mut self.page_mut(idx / PAGE_SIZE)[idx % PAGE_SIZE]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grow() {
+        let mut mem = Memory::from(vec![1, 2, 3]);
+        assert_eq!((mem.len(), mem[2], mem[3], mem[5000]), (3, 3, 0, 0));
+        mem[1000] = 6;
+        mem[5000] = 7;
+        assert_eq!((mem.len(), mem.dense_len(), mem[4999], mem[5000]), (5001, 1001, 0, 7));
+        assert_eq!(mem.iter().filter(|&x| x != 0).collect::<Vec<_>>(), [1, 2, 3, 6]);
+        assert_eq!(mem.sparse().collect::<Vec<_>>(), [(5000, 7)]);
+        let mut copy = Memory::from(mem.to_vec());
+        copy[5000] = 7;
+        assert_eq!(copy, mem);
+    }
+
+    #[test]
+    fn test_sparse() {
+        let mut mem: Memory = Memory::from(vec![1, 2, 3]);
+        mem[1 << 40] = 7;
+        mem[3 * PAGE_SIZE] = 8;
+        let stats = mem.stats();
+        assert_eq!((stats.dense_pages, stats.sparse_pages), (1, 2));
+        assert_eq!(stats.bytes, 3 * PAGE_SIZE * std::mem::size_of::<isize>());
+        assert_eq!((mem.len(), mem[1 << 40], mem[(1 << 40) - 1]), ((1 << 40) + 1, 7, 0));
+
+        // Filling the gap moves the following pages into the dense part.
+        let before = mem.clone();
+        mem[PAGE_SIZE] = 0;
+        mem[2 * PAGE_SIZE] = 0;
+        assert_eq!((mem.stats().dense_pages, mem.stats().sparse_pages), (4, 1));
+        assert_eq!((mem[3 * PAGE_SIZE], mem.dense_len()), (8, 3 * PAGE_SIZE + 1));
+        assert_eq!(mem, before);
+
+        use std::hash::BuildHasher;
+        let state = std::hash::RandomState::new();
+        assert_eq!(state.hash_one(&mem), state.hash_one(&before));
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let mut mem: Memory = (0..3 * PAGE_SIZE as isize).collect();
+        let snapshot = mem.clone();
+        assert_eq!(mem.shared_pages(), 3);
+        mem[PAGE_SIZE + 1] = -1;
+        assert_eq!(mem.shared_pages(), 2);
+        assert_eq!(snapshot[PAGE_SIZE + 1], PAGE_SIZE as isize + 1);
+        assert_eq!(mem[PAGE_SIZE + 1], -1);
+    }
+
+    #[test]
+    fn test_decode_cache() {
+        use crate::Param;
+
+        let mut mem = Memory::from(vec![1101, 1, 2, 3, 99]);
+        let add = Instruction::Add(Param::Imme(1), Param::Imme(2), Param::Addr(3));
+        assert_eq!(mem.decode(0), Ok(add));
+        assert_eq!(mem.decode(4), Ok(Instruction::Halt));
+        let mut snapshot = mem.clone();
+
+        // Overwriting an operand must not leave the stale instruction behind.
+        mem[3] = 4;
+        let add = Instruction::Add(Param::Imme(1), Param::Imme(2), Param::Addr(4));
+        assert_eq!(mem.decode(0), Ok(add));
+        mem[0] = 0;
+        assert_eq!(mem.decode(0), Err(ErrorKind::BadOpcode));
+        assert_eq!(mem.decode(4), Ok(Instruction::Halt));
+        assert_eq!(snapshot.decode(0).unwrap().params()[2], Param::Addr(3));
+    }
+}
@@ -0,0 +1,149 @@
+use super::{Intcode, Memory, Word, MAX_ADDR};
+use itertools::Itertools;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// The complete state of an [`Intcode`] machine. Snapshots share memory pages with the machine
+/// they were taken from, so taking one is cheap.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Snapshot<W: Word = isize> {
+    code: Memory<W>,
+    pc: usize,
+    ra: W,
+    input: VecDeque<W>,
+}
+
+impl<W: Word> Intcode<W> {
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot { code: self.code.clone(), pc: self.pc, ra: self.ra, input: self.input.clone() }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot<W>) {
+        self.code = snapshot.code.clone();
+        self.pc = snapshot.pc;
+        self.ra = snapshot.ra;
+        self.input = snapshot.input.clone();
+    }
+}
+
+impl<W: Word> From<&Snapshot<W>> for Intcode<W> {
+    fn from(snapshot: &Snapshot<W>) -> Self {
+        let mut program = Self::default();
+        program.restore(snapshot);
+        program
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+impl<W: Word> Snapshot<W> {
+    /// Writes the snapshot in a line based text format:
+    ///
+    /// ```text
+    /// intcode-snapshot 1
+    /// pc <pc>
+    /// ra <ra>
+    /// input <comma separated pending input>
+    /// code <comma separated dense memory>
+    /// sparse <comma separated addr=value pairs for the rest of memory>
+    /// ```
+    pub fn save(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "{HEADER}")?;
+        writeln!(w, "pc {}", self.pc)?;
+        writeln!(w, "ra {}", self.ra)?;
+        writeln!(w, "input {}", self.input.iter().join(","))?;
+        writeln!(w, "code {}", self.code.iter().join(","))?;
+        writeln!(w, "sparse {}", self.code.sparse().map(|(a, x)| format!("{a}={x}")).join(","))
+    }
+
+    pub fn load(r: impl BufRead) -> io::Result<Self> {
+        let mut lines = r.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid("not an intcode snapshot"));
+        }
+        let mut field = |name: &str| -> io::Result<String> {
+            let line = lines.next().transpose()?.unwrap_or_default();
+            match line.split_once(' ').unwrap_or((&line, "")) {
+                (key, value) if key == name => Ok(value.trim().to_string()),
+                _ => Err(invalid(format!("expected field `{name}`"))),
+            }
+        };
+        let number = |s: &str| -> io::Result<W> {
+            s.parse().map_err(|e| invalid(format!("bad number `{s}`: {e}")))
+        };
+        let list = |s: &str| -> io::Result<Vec<W>> {
+            s.split(',').filter(|e| !e.is_empty()).map(number).collect()
+        };
+        let addr = |s: &str| -> io::Result<usize> {
+            match s.parse() {
+                Ok(a) if a <= MAX_ADDR => Ok(a),
+                Ok(_) => Err(invalid(format!("address `{s}` out of range"))),
+                Err(e) => Err(invalid(format!("bad address `{s}`: {e}"))),
+            }
+        };
+        let pc = field("pc")?;
+        let ra = field("ra")?;
+        let input = list(&field("input")?)?.into();
+        let mut code: Memory<W> = list(&field("code")?)?.into();
+        for pair in field("sparse")?.split(',').filter(|e| !e.is_empty()) {
+            let (a, x) = pair.split_once('=').ok_or_else(|| invalid("bad sparse pair"))?;
+            code[addr(a)?] = number(x)?;
+        }
+        Ok(Self { pc: addr(&pc)?, ra: number(&ra)?, input, code })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore() {
+        let mut program =
+            Intcode::parse("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+        let mut outputs = program.run([]).take(5).collect::<Vec<_>>();
+        let snapshot = program.snapshot();
+        let rest = program.run([]).collect::<Vec<_>>();
+        assert!(program.halted());
+
+        program.restore(&snapshot);
+        assert_eq!(program.run([]).collect::<Vec<_>>(), rest);
+        outputs.extend(rest);
+        assert_eq!(
+            outputs,
+            [109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]
+        );
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut program = Intcode::parse("3,9,1002,9,3,9,4,9,99,0");
+        program.extend_input([11, 22, 33]);
+        assert_eq!(program.run([]).next(), Some(33));
+        let snapshot = program.snapshot();
+
+        let mut buf = vec![];
+        snapshot.save(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            text,
+            "intcode-snapshot 1\npc 8\nra 0\ninput 22,33\ncode 3,9,1002,9,3,9,4,9,99,33\nsparse \n"
+        );
+        let loaded = Snapshot::load(text.as_bytes()).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert_eq!(Intcode::from(&loaded), program);
+
+        let load = |text: &str| Snapshot::<isize>::load(text.as_bytes());
+        let head = "intcode-snapshot 1\npc 8\nra 0\ninput 22,33\ncode 3,9,1002,9,3,9,4,9,99,33\n";
+        assert!(load(head).is_err());
+        assert!(load(&format!("{head}sprase 5000=7\n")).is_err());
+        assert_eq!(load(&format!("{head}sparse 5000=7\n")).unwrap().code[5000], 7);
+        assert!(load(&format!("{head}sparse 18446744073709551615=7\n")).is_err());
+        assert!(load("intcode-snapshot 1\npc -1\n").is_err());
+        assert!(load("3,9,1002").is_err());
+    }
+}
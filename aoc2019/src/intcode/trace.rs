@@ -0,0 +1,181 @@
+use super::{Instruction, Intcode, Word};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// One executed instruction.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct TraceEntry<W = isize> {
+    pub pc: usize,
+    pub instruction: Instruction<W>,
+    operands: [W; 3],
+    /// Address and new value of the memory cell written by the instruction.
+    pub write: Option<(usize, W)>,
+}
+
+impl<W: Word> TraceEntry<W> {
+    /// Values of the operands read by the instruction, in order.
+    pub fn operands(&self) -> &[W] {
+        let n = self.instruction.params().len() - self.instruction.dest().is_some() as usize;
+        &self.operands[..n]
+    }
+}
+
+impl<W: Word> std::fmt::Display for TraceEntry<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = self.instruction.to_string();
+        write!(f, "{:>6}  {text:<32} ({})", self.pc, self.operands().iter().join(", "))?;
+        if let Some((addr, value)) = self.write {
+            write!(f, " [{addr}] <- {value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Word> Intcode<W> {
+    /// Reads the operands of the instruction at pc, before it executes.
+    pub(super) fn trace_entry(&self) -> Option<TraceEntry<W>> {
+        let instruction = self.fetch().ok()?;
+        let mut operands = [W::ZERO; 3];
+        let params = instruction.params();
+        let sources = params.len() - instruction.dest().is_some() as usize;
+        for (o, &p) in operands.iter_mut().zip(&params[..sources]) {
+            *o = self.get(p).ok()?;
+        }
+        let write = match instruction.dest() {
+            Some(p) => Some((self.addr(p).ok()?, W::ZERO)),
+            None => None,
+        };
+        Some(TraceEntry { pc: self.pc, instruction, operands, write })
+    }
+}
+
+/// A sink for [`Intcode::step_with`].
+pub trait Tracer<W = isize> {
+    /// Tracers that set this to false are never called and cost nothing.
+    const ENABLED: bool = true;
+
+    fn record(&mut self, entry: &TraceEntry<W>);
+}
+
+impl<W> Tracer<W> for () {
+    const ENABLED: bool = false;
+
+    fn record(&mut self, _: &TraceEntry<W>) {}
+}
+
+impl<W: Word> Tracer<W> for Vec<TraceEntry<W>> {
+    fn record(&mut self, entry: &TraceEntry<W>) {
+        self.push(*entry);
+    }
+}
+
+impl<W, A: Tracer<W>, B: Tracer<W>> Tracer<W> for (A, B) {
+    const ENABLED: bool = A::ENABLED || B::ENABLED;
+
+    fn record(&mut self, entry: &TraceEntry<W>) {
+        self.0.record(entry);
+        self.1.record(entry);
+    }
+}
+
+/// Writes one line per executed instruction.
+pub struct TraceWriter<W: Write>(pub W);
+
+impl<W: Word, T: Write> Tracer<W> for TraceWriter<T> {
+    fn record(&mut self, entry: &TraceEntry<W>) {
+        writeln!(self.0, "{entry}").expect("failed to write trace");
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Profiler<W = isize> {
+    pub steps: usize,
+    pub opcodes: HashMap<&'static str, usize>,
+    /// Hit count and last seen instruction for each executed address.
+    pub hits: HashMap<usize, (usize, Instruction<W>)>,
+}
+
+impl<W: Word> Profiler<W> {
+    /// The `n` most executed addresses with their hit counts, most frequent first.
+    pub fn hottest(&self, n: usize) -> Vec<(usize, usize, Instruction<W>)> {
+        let mut hits: Vec<_> = self.hits.iter().map(|(&pc, &(c, i))| (pc, c, i)).collect();
+        hits.sort_by_key(|&(pc, c, _)| (std::cmp::Reverse(c), pc));
+        hits.truncate(n);
+        hits
+    }
+}
+
+impl<W: Word> Tracer<W> for Profiler<W> {
+    fn record(&mut self, entry: &TraceEntry<W>) {
+        self.steps += 1;
+        *self.opcodes.entry(entry.instruction.mnemonic()).or_default() += 1;
+        let hit = self.hits.entry(entry.pc).or_insert((0, entry.instruction));
+        *hit = (hit.0 + 1, entry.instruction);
+    }
+}
+
+impl<W: Word> std::fmt::Display for Profiler<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |c: usize| 100.0 * c as f64 / self.steps.max(1) as f64;
+        writeln!(f, "Total steps: {}", self.steps)?;
+        writeln!(f, "Opcodes:")?;
+        for (op, c) in self.opcodes.iter().sorted_by_key(|&(op, &c)| (std::cmp::Reverse(c), op)) {
+            writeln!(f, "  {op:<4} {c:>12} {:>6.2}%", percent(*c))?;
+        }
+        writeln!(f, "Hottest addresses:")?;
+        for (pc, c, instruction) in self.hottest(10) {
+            writeln!(f, "  {pc:>6} {c:>12} {:>6.2}%  {instruction}", percent(c))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Param};
+
+    #[test]
+    fn test_trace() {
+        let mut program = Intcode::parse("3,9,1002,9,3,9,4,9,99,0");
+        program.push_input(33);
+        let mut trace = vec![];
+        assert_eq!(program.resume_with(&mut trace), Ok(Event::Output(99)));
+        let lines = trace.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "     0  in [9]                           () [9] <- 33",
+                "     2  mul [9], 3, [9]                  (33, 3) [9] <- 99",
+                "     6  out [9]                          (99)",
+            ]
+        );
+        assert_eq!(
+            trace[1].instruction,
+            Instruction::Mul(Param::Addr(9), Param::Imme(3), Param::Addr(9))
+        );
+    }
+
+    #[test]
+    fn test_trace_needs_input() {
+        let mut program = Intcode::parse("3,0,99");
+        let mut trace = vec![];
+        assert_eq!(program.resume_with(&mut trace), Ok(Event::NeedsInput));
+        assert!(trace.is_empty());
+    }
+
+    #[test]
+    fn test_profiler() {
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut program = Intcode::parse(quine);
+        let mut profiler = Profiler::default();
+        while program.resume_with(&mut profiler) != Ok(Event::Halted) {}
+        // 16 iterations of a five instruction loop, then halt.
+        assert_eq!(profiler.steps, 16 * 5 + 1);
+        assert_eq!(profiler.opcodes["out"], 16);
+        assert_eq!(profiler.opcodes["hlt"], 1);
+        assert_eq!(profiler.hottest(1), [(0, 16, Instruction::Rela(Param::Imme(1)))]);
+        assert_eq!(profiler.hottest(100).len(), 6);
+    }
+}
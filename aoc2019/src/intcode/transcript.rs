@@ -0,0 +1,284 @@
+use super::{Deferred, ErrorKind, Event, Intcode, IntcodeError, Word};
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+
+const HEADER: &str = "intcode-transcript 1";
+
+/// One I/O event, with the number of instructions executed before it.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Record<W = isize> {
+    Input { step: usize, value: W },
+    Output { step: usize, value: W },
+    Halt { step: usize },
+}
+
+impl<W: Word> std::fmt::Display for Record<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input { step, value } => write!(f, "in {step} {value}"),
+            Self::Output { step, value } => write!(f, "out {step} {value}"),
+            Self::Halt { step } => write!(f, "halt {step}"),
+        }
+    }
+}
+
+/// Everything that went in and out of a machine during a session, so that it can be replayed
+/// against the same program later.
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Transcript<W = isize> {
+    pub records: Vec<Record<W>>,
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum ReplayError<W = isize> {
+    Fault(IntcodeError<W>),
+    /// At `step`, the program did something other than the record at `index`, which is `None`
+    /// past the end of the transcript.
+    Mismatch {
+        index: usize,
+        step: usize,
+        expected: Option<Record<W>>,
+        found: Event<W>,
+    },
+}
+
+impl<W: Word> std::fmt::Display for ReplayError<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fault(e) => write!(f, "replay failed: {e}"),
+            Self::Mismatch { index, step, expected, found } => {
+                write!(f, "transcript mismatch at record {index}: expected ")?;
+                match expected {
+                    Some(record) => write!(f, "`{record}`")?,
+                    None => write!(f, "end of transcript")?,
+                }
+                match found {
+                    Event::Output(x) => write!(f, ", found `out {step} {x}`"),
+                    Event::NeedsInput => write!(f, ", found `in {step}`"),
+                    _ => write!(f, ", found `halt {step}`"),
+                }
+            }
+        }
+    }
+}
+
+impl<W: Word> std::error::Error for ReplayError<W> {}
+
+impl<W: Word> Intcode<W> {
+    /// Like [`Intcode::run`], but appends every input consumed and output produced to
+    /// `transcript`.
+    pub fn run_recorded<'a>(
+        &'a mut self,
+        input: impl IntoIterator<Item = W> + 'a,
+        transcript: &'a RefCell<Transcript<W>>,
+    ) -> impl Iterator<Item = W> + 'a {
+        let mut input = input.into_iter();
+        let mut step = 0;
+        let mut done = false;
+        let record = move |r| transcript.borrow_mut().records.push(r);
+        std::iter::from_fn(move || {
+            while !done {
+                match self.step().unwrap_or_else(|e| panic!("{e}")) {
+                    Event::Continue => step += 1,
+                    Event::Output(value) => {
+                        step += 1;
+                        record(Record::Output { step, value });
+                        return Some(value);
+                    }
+                    Event::NeedsInput => match input.next() {
+                        Some(value) => {
+                            record(Record::Input { step, value });
+                            self.push_input(value);
+                        }
+                        None => panic!("{}", self.error(ErrorKind::InputExhausted)),
+                    },
+                    Event::Halted => {
+                        record(Record::Halt { step });
+                        done = true;
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    pub fn deferred_run_recorded<'a>(
+        &'a mut self,
+        transcript: &'a RefCell<Transcript<W>>,
+    ) -> Deferred<'a, W> {
+        let mut step = 0;
+        Deferred::new(self, move |event, input| {
+            let record = match (event, input) {
+                (Event::Continue, _) => {
+                    step += 1;
+                    return;
+                }
+                (Event::Output(value), _) => {
+                    step += 1;
+                    Record::Output { step, value }
+                }
+                (Event::NeedsInput, Some(value)) => Record::Input { step, value },
+                (Event::NeedsInput, None) => return,
+                (Event::Halted, _) => Record::Halt { step },
+            };
+            transcript.borrow_mut().records.push(record);
+        })
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+impl<W: Word> Transcript<W> {
+    pub fn inputs(&self) -> impl Iterator<Item = W> + '_ {
+        self.records.iter().filter_map(|r| match *r {
+            Record::Input { value, .. } => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = W> + '_ {
+        self.records.iter().filter_map(|r| match *r {
+            Record::Output { value, .. } => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Runs `program`, feeding it the recorded inputs, and checks that it reads and writes at the
+    /// same steps as recorded.
+    pub fn replay(&self, program: &mut Intcode<W>) -> Result<(), ReplayError<W>> {
+        let mut records = self.records.iter().copied().enumerate().peekable();
+        let mut step = 0;
+        loop {
+            let found = match program.step().map_err(ReplayError::Fault)? {
+                Event::Continue => {
+                    step += 1;
+                    continue;
+                }
+                Event::Output(value) => {
+                    step += 1;
+                    Record::Output { step, value }
+                }
+                Event::NeedsInput => match records.peek() {
+                    Some(&(_, r @ Record::Input { step: s, value })) if s == step => {
+                        program.push_input(value);
+                        r
+                    }
+                    _ => {
+                        let (index, expected) = records.next().unzip();
+                        let index = index.unwrap_or(self.records.len());
+                        let found = Event::NeedsInput;
+                        return Err(ReplayError::Mismatch { index, step, expected, found });
+                    }
+                },
+                Event::Halted => Record::Halt { step },
+            };
+            let (index, expected) = records.next().unzip();
+            if expected != Some(found) {
+                let index = index.unwrap_or(self.records.len());
+                let found = match found {
+                    Record::Output { value, .. } => Event::Output(value),
+                    _ => Event::Halted,
+                };
+                return Err(ReplayError::Mismatch { index, step, expected, found });
+            }
+            if let Record::Halt { .. } = found {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Writes the transcript in a line based text format:
+    ///
+    /// ```text
+    /// intcode-transcript 1
+    /// in <step> <value>
+    /// out <step> <value>
+    /// halt <step>
+    /// ```
+    pub fn save(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "{HEADER}")?;
+        for record in &self.records {
+            writeln!(w, "{record}")?;
+        }
+        Ok(())
+    }
+
+    pub fn load(r: impl BufRead) -> io::Result<Self> {
+        let mut lines = r.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid("not an intcode transcript"));
+        }
+        let mut records = vec![];
+        for line in lines {
+            let line = line?;
+            let bad = || invalid(format!("bad record `{line}`"));
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let step = || fields.get(1).and_then(|s| s.parse().ok()).ok_or_else(bad);
+            let value = || fields.get(2).and_then(|s| s.parse().ok()).ok_or_else(bad);
+            records.push(match fields[..] {
+                [] => continue,
+                ["in", _, _] => Record::Input { step: step()?, value: value()? },
+                ["out", _, _] => Record::Output { step: step()?, value: value()? },
+                ["halt", _] => Record::Halt { step: step()? },
+                _ => return Err(bad()),
+            });
+        }
+        Ok(Self { records })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_intcode;
+
+    const PROGRAM: &str = "3,9,1002,9,3,9,4,9,99,0";
+
+    #[test]
+    fn test_record() {
+        let mut program = Intcode::parse(PROGRAM);
+        let transcript = RefCell::default();
+        assert_eq!(program.run_recorded([11], &transcript).collect::<Vec<_>>(), [33]);
+        let transcript = transcript.into_inner();
+        let mut buf = vec![];
+        transcript.save(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(text, "intcode-transcript 1\nin 0 11\nout 3 33\nhalt 3\n");
+        assert_eq!(Transcript::load(text.as_bytes()).unwrap(), transcript);
+        assert!(Transcript::<isize>::load("intcode-transcript 1\nout 3\n".as_bytes()).is_err());
+
+        assert_eq!(transcript.replay(&mut Intcode::parse(PROGRAM)), Ok(()));
+        let err = transcript.replay(&mut Intcode::parse("3,9,1002,9,4,9,4,9,99,0")).unwrap_err();
+        let expected = Some(Record::Output { step: 3, value: 33 });
+        let found = Event::Output(44);
+        assert_eq!(err, ReplayError::Mismatch { index: 1, step: 3, expected, found });
+        assert_eq!(
+            err.to_string(),
+            "transcript mismatch at record 1: expected `out 3 33`, found `out 3 44`"
+        );
+        let err = transcript.replay(&mut Intcode::parse("3,9,3,9,99")).unwrap_err();
+        assert!(matches!(err, ReplayError::Mismatch { index: 1, step: 1, .. }));
+    }
+
+    #[test]
+    fn test_deferred_recorded() {
+        let mut program = Intcode::parse("3,13,4,13,3,13,1001,13,1,13,4,13,99");
+        let transcript = RefCell::default();
+        let deferred = program.deferred_run_recorded(&transcript);
+        deferred.send(5);
+        assert_eq!(deferred.iter().next(), Some(5));
+        deferred.send(7);
+        assert_eq!(deferred.iter().collect::<Vec<_>>(), [8]);
+        drop(deferred);
+        let transcript = transcript.into_inner();
+        assert_eq!(transcript.inputs().collect::<Vec<_>>(), [5, 7]);
+        assert_eq!(transcript.outputs().collect::<Vec<_>>(), [5, 8]);
+
+        let path = std::env::temp_dir().join(format!("transcript-{}.txt", std::process::id()));
+        transcript.save(std::fs::File::create(&path).unwrap()).unwrap();
+        assert_intcode!("3,13,4,13,3,13,1001,13,1,13,4,13,99", transcript = &path);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+use super::{to_addr, Cfg, Edge, Instruction, Intcode, Memory, Param, Word};
+use itertools::Itertools;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+impl<W: Word> Intcode<W> {
+    /// A machine that resumes at `pc` with the given memory and relative base, as transpiled code
+    /// does when it hands over to the interpreter.
+    pub fn from_parts(code: impl Into<Memory<W>>, pc: usize, ra: W) -> Self {
+        Self { code: code.into(), pc, ra, ..Default::default() }
+    }
+
+    /// Translates the program into Rust source defining
+    ///
+    /// ```text
+    /// pub fn run(input: impl IntoIterator<Item = W>, mut output: impl FnMut(W))
+    /// ```
+    ///
+    /// which behaves like [`Intcode::run`]. Each basic block becomes a match arm on pc. Code that
+    /// writes into its own instructions, or jumps anywhere but the start of a block, continues in
+    /// the interpreter, which the source refers to as `utils::Intcode`.
+    pub fn transpile(&self) -> String {
+        Transpiler::new(self).emit()
+    }
+}
+
+struct Transpiler<W: Word> {
+    cfg: Cfg<W>,
+    memory: Memory<W>,
+    /// Addresses holding opcodes and operands, which must not be written without falling back.
+    code: BTreeSet<usize>,
+    ty: &'static str,
+}
+
+impl<W: Word> Transpiler<W> {
+    fn new(program: &Intcode<W>) -> Self {
+        let cfg = program.cfg();
+        let code = cfg
+            .blocks
+            .values()
+            .flat_map(|b| &b.instructions)
+            .flat_map(|&(addr, i)| {
+                // Operands that the program overwrites were resolved to reads of themselves.
+                let operands = i.params().into_iter().enumerate().filter_map(move |(j, p)| {
+                    let a = addr + 1 + j;
+                    (W::try_from(a).ok().map(Param::Addr) != Some(p)).then_some(a)
+                });
+                std::iter::once(addr).chain(operands)
+            })
+            .collect();
+        let ty = std::any::type_name::<W>();
+        Self { cfg, memory: program.code.clone(), code, ty }
+    }
+
+    fn emit(&self) -> String {
+        let ty = self.ty;
+        let mut out = String::new();
+        writeln!(out, "// Transpiled from an Intcode program. Do not edit.").unwrap();
+        writeln!(out).unwrap();
+        // Helpers and `mut`s go unused in some programs, and straight-line programs never loop.
+        let lints = "dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop";
+        writeln!(out, "#[allow({lints})]").unwrap();
+        writeln!(
+            out,
+            "pub fn run(input: impl IntoIterator<Item = {ty}>, mut output: impl FnMut({ty})) {{"
+        )
+        .unwrap();
+        let ranges = self
+            .code
+            .iter()
+            .copied()
+            .chunk_by({
+                let mut prev = None;
+                move |&a| {
+                    let start = match prev {
+                        Some((s, p)) if p + 1 == a => s,
+                        _ => a,
+                    };
+                    prev = Some((start, a));
+                    start
+                }
+            })
+            .into_iter()
+            .map(|(start, run)| format!("{start}..={}", run.last().unwrap()))
+            .join(" | ");
+        let ranges = if ranges.is_empty() { "_ if false".to_string() } else { ranges };
+        write!(
+            out,
+            r#"    fn is_code(a: usize) -> bool {{
+        matches!(a, {ranges})
+    }}
+    fn addr(a: {ty}) -> usize {{
+        usize::try_from(a).expect("negative address")
+    }}
+    fn get(mem: &utils::Memory<{ty}>, a: usize) -> {ty} {{
+        mem[a]
+    }}
+    fn set(mem: &mut utils::Memory<{ty}>, a: usize, v: {ty}) {{
+        mem[a] = v;
+    }}
+    let mut input = input.into_iter();
+"#
+        )
+        .unwrap();
+        let dense = self.memory.iter().join(", ");
+        writeln!(out, "    let mut mem: utils::Memory<{ty}> = vec![{dense}].into();").unwrap();
+        for (a, x) in self.memory.sparse() {
+            writeln!(out, "    set(&mut mem, {a}, {x});").unwrap();
+        }
+        writeln!(out, "    let mut ra: {ty} = 0;").unwrap();
+        writeln!(out, "    let mut pc: usize = 0;").unwrap();
+        writeln!(out, "    loop {{").unwrap();
+        writeln!(out, "        match pc {{").unwrap();
+        for block in self.cfg.blocks.values() {
+            writeln!(out, "            {} => {{", block.start).unwrap();
+            let mut terminated = false;
+            for &(addr, instruction) in &block.instructions {
+                let lines = self.instruction(addr, &instruction);
+                for line in &lines {
+                    writeln!(out, "                {line}").unwrap();
+                }
+                // Whatever follows a write into code, a halt or a jump that is always taken is never
+                // reached.
+                if matches!(
+                    lines.last().map(String::as_str),
+                    Some("break;" | "return;" | "continue;")
+                ) {
+                    terminated = true;
+                    break;
+                }
+            }
+            let (addr, last) = block.instructions.last().unwrap();
+            let next = addr + last.width();
+            // Jumps already set pc, everything else continues at the next instruction.
+            if !terminated && !block.edges.iter().any(|e| !matches!(e, Edge::Next(_))) {
+                writeln!(out, "                pc = {next};").unwrap();
+            }
+            writeln!(out, "            }}").unwrap();
+        }
+        writeln!(out, "            _ => break,").unwrap();
+        writeln!(out, "        }}").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {{")
+            .unwrap();
+        writeln!(out, "        output(x);").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    /// `a` as a `usize` expression: a literal when it fits, otherwise converted at run time,
+    /// which fails the same way the interpreter does.
+    fn addr(&self, a: W) -> String {
+        match to_addr(a) {
+            Ok(a) => a.to_string(),
+            Err(_) => format!("addr({a}{})", self.ty),
+        }
+    }
+
+    fn read(&self, p: Param<W>) -> String {
+        let ty = self.ty;
+        match p {
+            Param::Imme(v) => format!("{v}{ty}"),
+            Param::Addr(a) => format!("get(&mem, {})", self.addr(a)),
+            Param::Rela(k) => format!("get(&mem, addr(ra.checked_add({k}).expect(\"overflow\")))"),
+        }
+    }
+
+    /// Statements that store `value` to `dest`, then hand over to the interpreter at `next` if
+    /// they wrote into code.
+    fn write(&self, dest: Param<W>, value: &str, next: usize) -> Vec<String> {
+        let (a, check) = match dest {
+            Param::Addr(a) => {
+                let check = to_addr(a).ok().filter(|a| self.code.contains(a));
+                (self.addr(a), check.map(|_| "true".to_string()))
+            }
+            _ => ("a".to_string(), Some("is_code(a)".to_string())),
+        };
+        let mut lines = vec![];
+        if let Param::Rela(k) = dest {
+            lines.push(format!("let a = addr(ra.checked_add({k}).expect(\"overflow\"));"));
+        }
+        lines.push(format!("set(&mut mem, {a}, {value});"));
+        match check.as_deref() {
+            Some("true") => lines.extend([format!("pc = {next};"), "break;".to_string()]),
+            Some(check) => lines.push(format!("if {check} {{ pc = {next}; break; }}")),
+            None => {}
+        }
+        lines
+    }
+
+    fn instruction(&self, addr: usize, instruction: &Instruction<W>) -> Vec<String> {
+        let ty = self.ty;
+        let next = addr + instruction.width();
+        let binary = |a, b, c, op: &str| {
+            let mut lines = vec![
+                format!("let x: {ty} = {};", self.read(a)),
+                format!("let y: {ty} = {};", self.read(b)),
+            ];
+            lines.extend(self.write(c, op, next));
+            lines
+        };
+        let mut lines = vec![format!("// {addr}: {instruction}")];
+        lines.extend(match *instruction {
+            Instruction::Add(a, b, c) => binary(a, b, c, "x.checked_add(y).expect(\"overflow\")"),
+            Instruction::Mul(a, b, c) => binary(a, b, c, "x.checked_mul(y).expect(\"overflow\")"),
+            Instruction::Tlt(a, b, c) => binary(a, b, c, &format!("(x < y) as {ty}")),
+            Instruction::Teq(a, b, c) => binary(a, b, c, &format!("(x == y) as {ty}")),
+            Instruction::Input(a) => {
+                let mut lines =
+                    vec!["let x = input.next().expect(\"input exhausted\");".to_string()];
+                lines.extend(self.write(a, "x", next));
+                lines
+            }
+            Instruction::Output(a) => vec![format!("output({});", self.read(a))],
+            Instruction::Jit(a, b) | Instruction::Jif(a, b) => {
+                let jit = matches!(instruction, Instruction::Jit(..));
+                let target = match b {
+                    Param::Imme(t) => self.addr(t),
+                    _ => format!("addr({})", self.read(b)),
+                };
+                match a {
+                    // Constant conditions are decided here rather than in the generated code.
+                    Param::Imme(c) if (c != W::ZERO) == jit => {
+                        vec![format!("pc = {target};"), "continue;".to_string()]
+                    }
+                    Param::Imme(_) => vec![format!("pc = {next};")],
+                    _ => vec![
+                        format!("if {} {} 0 {{", self.read(a), if jit { "!=" } else { "==" }),
+                        format!("    pc = {target};"),
+                        "    continue;".to_string(),
+                        "}".to_string(),
+                        format!("pc = {next};"),
+                    ],
+                }
+            }
+            Instruction::Rela(a) => {
+                vec![format!("ra = ra.checked_add({}).expect(\"overflow\");", self.read(a))]
+            }
+            Instruction::Halt => vec!["return;".to_string()],
+        });
+        lines
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::num::ParseIntError;
+use std::ops::{Add, Div, Mul, Neg, Rem};
+use std::str::FromStr;
+
+/// A signed integer type an [`Intcode`](super::Intcode) machine computes with. Arithmetic that
+/// doesn't fit in the word is reported as [`ErrorKind::Overflow`](super::ErrorKind::Overflow).
+pub trait Word:
+    Copy
+    + Default
+    + Debug
+    + Display
+    + Hash
+    + Ord
+    + Send
+    + 'static
+    + From<i8>
+    + From<bool>
+    + FromStr<Err = ParseIntError>
+    + TryFrom<usize>
+    + TryInto<usize>
+    + Add<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + Neg<Output = Self>
+{
+    const ZERO: Self;
+
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                const ZERO: Self = 0;
+
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_add(self, rhs)
+                }
+
+                fn checked_mul(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(i32, i64, i128, isize);
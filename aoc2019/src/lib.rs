@@ -1,8 +1,14 @@
 pub mod batch_lines;
 pub mod grid;
 pub mod intcode;
+pub mod network;
+pub mod ocr;
+pub mod scheduler;
+pub mod springscript;
 pub mod terminal;
 
 pub use batch_lines::*;
 pub use grid::*;
 pub use intcode::*;
+pub use network::*;
+pub use scheduler::*;
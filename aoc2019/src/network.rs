@@ -0,0 +1,136 @@
+use crate::{Intcode, IntcodeError, Scheduler, Status};
+use std::collections::HashMap;
+
+/// A named connection carrying every output of machine `from` to machine `to`, or out of the
+/// network if `to` is `None`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Link {
+    pub name: String,
+    pub from: usize,
+    pub to: Option<usize>,
+}
+
+/// Intcode machines wired into an arbitrary directed graph. A machine with several outgoing
+/// edges sends each output along all of them, and one with several incoming edges reads the
+/// values in the order they arrive.
+#[derive(Debug, Default, Clone)]
+pub struct Network {
+    scheduler: Scheduler,
+    links: Vec<Link>,
+    observed: HashMap<String, Vec<isize>>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a machine and returns its id.
+    pub fn machine(&mut self, program: Intcode) -> usize {
+        self.scheduler.machines.push(program);
+        self.scheduler.machines.len() - 1
+    }
+
+    /// Queues input for machine `id`, such as its phase setting.
+    pub fn seed(&mut self, id: usize, values: impl IntoIterator<Item = isize>) {
+        self.scheduler.send(id, values);
+    }
+
+    pub fn connect(&mut self, name: &str, from: usize, to: usize) {
+        self.edge(name, from, Some(to));
+    }
+
+    /// Adds an edge out of the network, whose values can only be observed.
+    pub fn output(&mut self, name: &str, from: usize) {
+        self.edge(name, from, None);
+    }
+
+    fn edge(&mut self, name: &str, from: usize, to: Option<usize>) {
+        assert!(!self.observed.contains_key(name), "duplicate edge {name}");
+        self.observed.insert(name.to_string(), vec![]);
+        self.links.push(Link { name: name.to_string(), from, to });
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Runs until every machine has halted or is waiting for input that will never come.
+    pub fn run(&mut self) -> Result<Status, (usize, IntcodeError)> {
+        let Self { scheduler, links, observed } = self;
+        scheduler.run(|router, id, x| {
+            for link in links.iter().filter(|l| l.from == id) {
+                observed.get_mut(&link.name).unwrap().push(x);
+                if let Some(to) = link.to {
+                    router.send(to, [x]);
+                }
+            }
+        })
+    }
+
+    /// Every value sent along the edge called `name` so far.
+    pub fn observed(&self, name: &str) -> &[isize] {
+        self.observed.get(name).unwrap_or_else(|| panic!("no edge {name}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs each input plus one, until it outputs 5 or more.
+    const INC: &str = r"
+    loop:
+        in [x]
+        add [x], 1, [x]
+        out [x]
+        lt [x], 5, [t]
+        jit [t], loop
+        hlt
+    x:  .data 0
+    t:  .data 0
+    ";
+
+    #[test]
+    fn test_cycle() {
+        let mut network = Network::new();
+        let a = network.machine(Intcode::assemble(INC).unwrap());
+        let b = network.machine(Intcode::assemble(INC).unwrap());
+        network.connect("a->b", a, b);
+        network.connect("b->a", b, a);
+        network.seed(a, [0]);
+        assert_eq!(network.run(), Ok(Status::Halted));
+        assert_eq!(network.observed("a->b"), [1, 3, 5]);
+        assert_eq!(network.observed("b->a"), [2, 4, 6]);
+    }
+
+    #[test]
+    fn test_fan_out_fan_in() {
+        let mut network = Network::new();
+        let source = network.machine(Intcode::parse("104,5,99"));
+        let inc = network.machine(Intcode::parse("3,9,1001,9,1,9,4,9,99,0"));
+        let double = network.machine(Intcode::parse("3,9,1002,9,2,9,4,9,99,0"));
+        let sum = network.machine(Intcode::parse("3,11,3,12,1,11,12,11,4,11,99,0,0"));
+        network.connect("inc", source, inc);
+        network.connect("double", source, double);
+        network.connect("inc->sum", inc, sum);
+        network.connect("double->sum", double, sum);
+        network.output("sum", sum);
+        network.output("tap", double);
+        assert_eq!(network.run(), Ok(Status::Halted));
+        assert_eq!(network.observed("sum"), [16]);
+        assert_eq!(network.observed("tap"), [10]);
+        assert_eq!(network.links().len(), 6);
+    }
+
+    #[test]
+    fn test_idle() {
+        // The second machine waits for a second input that never comes.
+        let mut network = Network::new();
+        let a = network.machine(Intcode::parse("104,1,99"));
+        let b = network.machine(Intcode::parse("3,0,3,0,99"));
+        network.connect("a->b", a, b);
+        assert_eq!(network.run(), Ok(Status::Idle));
+        assert_eq!(network.observed("a->b"), [1]);
+    }
+}
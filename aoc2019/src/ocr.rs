@@ -0,0 +1,134 @@
+use crate::{Grid, Point};
+use std::collections::HashSet;
+
+/// The 6 rows high font, mostly 4 columns wide.
+const SMALL: &[(char, &[&str])] = &[
+    ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', &["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', &["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// The 10 rows high font, 6 columns wide.
+#[rustfmt::skip]
+const LARGE: &[(char, &[&str])] = &[
+    ('A', &["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+    ('B', &["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+    ('C', &[".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+    ('E', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('F', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('G', &[".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+    ('H', &["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+    ('J', &["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", "#...#.", ".###.."]),
+    ('K', &["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+    ('L', &["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('N', &["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+    ('P', &["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('R', &["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+    ('X', &["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+    ('Z', &["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+];
+
+/// Reads the letters drawn with `#` in `grid`, ignoring the margins around them. Returns `None`
+/// unless every letter is in one of the fonts.
+pub fn read(grid: &Grid<char>) -> Option<String> {
+    let points = (0..grid.h).flat_map(|x| (0..grid.w).map(move |y| Point::from((x, y))));
+    read_points(points.filter(|&p| grid[p] == '#'))
+}
+
+/// Like [`read`], for the set of lit points.
+pub fn read_points(points: impl IntoIterator<Item = Point>) -> Option<String> {
+    let points: HashSet<Point> = points.into_iter().collect();
+    let top = points.iter().map(|p| p.0).min()?;
+    let bottom = points.iter().map(|p| p.0).max()?;
+    let left = points.iter().map(|p| p.1).min()?;
+    let right = points.iter().map(|p| p.1).max()?;
+    let font = match bottom - top + 1 {
+        6 => SMALL,
+        10 => LARGE,
+        _ => return None,
+    };
+    let column = |y: isize| -> String {
+        (top..=bottom).map(|x| if points.contains(&Point(x, y)) { '#' } else { '.' }).collect()
+    };
+    // Letters are separated by blank columns, and have none of their own.
+    let mut text = String::new();
+    let mut glyph: Vec<String> = vec![];
+    for y in left..=right + 1 {
+        let col = column(y);
+        if col.contains('#') {
+            glyph.push(col);
+            continue;
+        }
+        if glyph.is_empty() {
+            continue;
+        }
+        let rows: Vec<String> =
+            (0..font[0].1.len()).map(|i| glyph.iter().map(|c| &c[i..=i]).collect()).collect();
+        let (c, _) = font.iter().find(|(_, g)| g.iter().eq(rows.iter()))?;
+        text.push(*c);
+        glyph.clear();
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws `text` with the given spacing between letters, and a margin all around.
+    fn draw(font: &[(char, &[&str])], text: &str, spacing: usize) -> Grid<char> {
+        let mut rows = vec![String::new(); font[0].1.len()];
+        for c in text.chars() {
+            let (_, glyph) = font.iter().find(|(f, _)| *f == c).unwrap();
+            for (row, g) in rows.iter_mut().zip(glyph.iter()) {
+                *row += &".".repeat(spacing);
+                *row += g;
+            }
+        }
+        let w = rows[0].len() + 2;
+        let mut buf = vec![vec!['.'; w]];
+        buf.extend(rows.iter().map(|r| format!(".{r}.").chars().collect()));
+        buf.push(vec!['.'; w]);
+        Grid::from(buf)
+    }
+
+    #[test]
+    fn test_small() {
+        let alphabet: String = SMALL.iter().map(|(c, _)| c).collect();
+        assert_eq!(read(&draw(SMALL, &alphabet, 1)).as_deref(), Some(alphabet.as_str()));
+        assert_eq!(read(&draw(SMALL, "ZYBLK", 1)).as_deref(), Some("ZYBLK"));
+    }
+
+    #[test]
+    fn test_large() {
+        let alphabet: String = LARGE.iter().map(|(c, _)| c).collect();
+        assert_eq!(read(&draw(LARGE, &alphabet, 2)).as_deref(), Some(alphabet.as_str()));
+        let l = (-4..5).map(|x| Point(x, 0)).chain((0..6).map(|y| Point(5, y)));
+        assert_eq!(read_points(l).as_deref(), Some("L"));
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert_eq!(read(&Grid::new('.', 6, 6)), None);
+        let mut grid = draw(SMALL, "AB", 1);
+        grid.buf[1][3] = '.';
+        assert_eq!(read(&grid), None);
+        // The 8 rows high letters of the 2018 day 10 example aren't in either font.
+        assert_eq!(read_points((0..8).map(|x| Point(x, 0))), None);
+    }
+}
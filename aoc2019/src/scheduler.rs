@@ -0,0 +1,158 @@
+use crate::{Event, Intcode, IntcodeError};
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Status {
+    /// A full round passed in which no machine had pending input or produced output.
+    Idle,
+    /// Every machine has halted.
+    Halted,
+}
+
+/// Delivers input to the machines of a [`Scheduler`] from within an output handler.
+pub struct Router<'a> {
+    machines: &'a mut [Intcode],
+}
+
+impl Router<'_> {
+    pub fn send(&mut self, dst: usize, values: impl IntoIterator<Item = isize>) {
+        self.machines[dst].extend_input(values);
+    }
+
+    pub fn count(&self) -> usize {
+        self.machines.len()
+    }
+}
+
+/// Runs many Intcode machines on one thread, one turn each in index order.
+#[derive(Debug, Default, Clone)]
+pub struct Scheduler {
+    pub machines: Vec<Intcode>,
+    /// Fed once per turn to a machine that asks for input while its queue is empty. Without it,
+    /// such a machine simply ends its turn.
+    pub idle_input: Option<isize>,
+}
+
+impl Scheduler {
+    pub fn new(machines: Vec<Intcode>) -> Self {
+        Self { machines, idle_input: None }
+    }
+
+    pub fn send(&mut self, dst: usize, values: impl IntoIterator<Item = isize>) {
+        self.machines[dst].extend_input(values);
+    }
+
+    /// Round-robins the machines until they are idle or halted. `on_output` is called with the
+    /// router, the id of the machine and the value it produced.
+    pub fn run(
+        &mut self,
+        mut on_output: impl FnMut(&mut Router, usize, isize),
+    ) -> Result<Status, (usize, IntcodeError)> {
+        loop {
+            let mut idle = true;
+            let mut halted = true;
+            for id in 0..self.machines.len() {
+                if self.machines[id].halted() {
+                    continue;
+                }
+                halted = false;
+                idle &= self.machines[id].pending_input().is_empty();
+                let mut fed = false;
+                loop {
+                    match self.machines[id].resume().map_err(|e| (id, e))? {
+                        Event::Output(x) => {
+                            idle = false;
+                            on_output(&mut Router { machines: &mut self.machines }, id, x);
+                        }
+                        Event::NeedsInput => match self.idle_input {
+                            Some(x) if !fed => {
+                                fed = true;
+                                self.machines[id].push_input(x);
+                            }
+                            _ => break,
+                        },
+                        Event::Halted => break,
+                        Event::Continue => unreachable!(),
+                    }
+                }
+            }
+            if halted {
+                return Ok(Status::Halted);
+            }
+            if idle {
+                return Ok(Status::Idle);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs every input plus one.
+    const INC: &str = r"
+    loop:
+        in [x]
+        add [x], 1, [x]
+        out [x]
+        jit 1, loop
+    x:  .data 0
+    ";
+
+    // Echoes every input other than -1.
+    const POLL: &str = r"
+    loop:
+        in [x]
+        eq [x], -1, [t]
+        jit [t], loop
+        out [x]
+        jit 1, loop
+    x:  .data 0
+    t:  .data 0
+    ";
+
+    #[test]
+    fn test_ring() {
+        let mut scheduler = Scheduler::new(vec![Intcode::assemble(INC).unwrap(); 3]);
+        scheduler.send(0, [0]);
+        let mut seen = vec![];
+        let status = scheduler.run(|router, id, x| {
+            seen.push((id, x));
+            if x < 10 {
+                router.send((id + 1) % router.count(), [x]);
+            }
+        });
+        assert_eq!(status, Ok(Status::Idle));
+        assert_eq!(seen, (1..=10).map(|x| ((x as usize - 1) % 3, x)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_idle_input() {
+        let mut scheduler = Scheduler::new(vec![Intcode::assemble(POLL).unwrap(); 2]);
+        scheduler.idle_input = Some(-1);
+        let mut seen = vec![];
+        for x in [7, 8] {
+            scheduler.send(0, [x]);
+            let status = scheduler.run(|router, id, x| {
+                seen.push((id, x));
+                if id == 0 {
+                    router.send(1, [x]);
+                }
+            });
+            assert_eq!(status, Ok(Status::Idle));
+        }
+        assert_eq!(seen, [(0, 7), (1, 7), (0, 8), (1, 8)]);
+        assert!(scheduler.machines.iter().all(|m| m.pending_input().is_empty()));
+    }
+
+    #[test]
+    fn test_halted_and_errors() {
+        let mut scheduler = Scheduler::new(vec![Intcode::parse("104,1,99"); 2]);
+        let mut seen = vec![];
+        assert_eq!(scheduler.run(|_, id, x| seen.push((id, x))), Ok(Status::Halted));
+        assert_eq!(seen, [(0, 1), (1, 1)]);
+
+        let mut scheduler = Scheduler::new(vec![Intcode::parse("99"), Intcode::parse("42")]);
+        assert_eq!(scheduler.run(|_, _, _| {}).unwrap_err().0, 1);
+    }
+}
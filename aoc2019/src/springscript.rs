@@ -0,0 +1,513 @@
+use itertools::Itertools;
+use std::str::FromStr;
+
+/// Springscript programs may have at most this many instructions, not counting WALK or RUN.
+pub const MAX_INSTRUCTIONS: usize = 15;
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct ScriptError {
+    pub msg: String,
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+fn error<T>(msg: impl Into<String>) -> Result<T, ScriptError> {
+    Err(ScriptError { msg: msg.into() })
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Mode {
+    /// Sensors A to D.
+    Walk,
+    /// Sensors A to I.
+    Run,
+}
+
+impl Mode {
+    pub fn sensors(self) -> usize {
+        match self {
+            Self::Walk => 4,
+            Self::Run => 9,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Reg {
+    /// Whether there is ground this many tiles ahead, minus one. `Sensor(0)` is A.
+    Sensor(u8),
+    T,
+    J,
+}
+
+impl std::fmt::Display for Reg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sensor(s) => write!(f, "{}", (b'A' + s) as char),
+            Self::T => write!(f, "T"),
+            Self::J => write!(f, "J"),
+        }
+    }
+}
+
+impl FromStr for Reg {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            b"T" => Ok(Self::T),
+            b"J" => Ok(Self::J),
+            &[c @ b'A'..=b'I'] => Ok(Self::Sensor(c - b'A')),
+            _ => error(format!("unknown register `{s}`")),
+        }
+    }
+}
+
+/// `AND X Y` sets Y to X and Y, `OR X Y` to X or Y, and `NOT X Y` to not X.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Instr {
+    And(Reg, Reg),
+    Or(Reg, Reg),
+    Not(Reg, Reg),
+}
+
+impl std::fmt::Display for Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::And(x, y) => write!(f, "AND {x} {y}"),
+            Self::Or(x, y) => write!(f, "OR {x} {y}"),
+            Self::Not(x, y) => write!(f, "NOT {x} {y}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Script {
+    pub instructions: Vec<Instr>,
+    pub mode: Mode,
+}
+
+/// The script as the springdroid firmware reads it, one instruction per line.
+impl std::fmt::Display for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{instruction}")?;
+        }
+        writeln!(f, "{}", if self.mode == Mode::Walk { "WALK" } else { "RUN" })
+    }
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut instructions = vec![];
+        for line in s.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let words = line.split_whitespace().collect_vec();
+            let mode = match words[..] {
+                ["WALK"] => Mode::Walk,
+                ["RUN"] => Mode::Run,
+                [op, x, y] => {
+                    let (x, y) = (x.parse()?, y.parse()?);
+                    if let Reg::Sensor(_) = y {
+                        return error(format!("cannot write to sensor `{y}`"));
+                    }
+                    instructions.push(match op {
+                        "AND" => Instr::And(x, y),
+                        "OR" => Instr::Or(x, y),
+                        "NOT" => Instr::Not(x, y),
+                        _ => return error(format!("unknown instruction `{op}`")),
+                    });
+                    continue;
+                }
+                _ => return error(format!("bad line `{line}`")),
+            };
+            let script = Self { instructions, mode };
+            script.validate()?;
+            return Ok(script);
+        }
+        error("missing WALK or RUN")
+    }
+}
+
+impl Script {
+    /// Compiles a boolean expression over the sensors into a script that jumps whenever it is
+    /// true. See [`Expr`] for the syntax.
+    pub fn compile(expr: &str, mode: Mode) -> Result<Self, ScriptError> {
+        let expr: Expr = expr.parse()?;
+        let Some(code) = gen(&expr, Reg::J, Some(Reg::T), [true; 2], true) else {
+            return error(format!("`{expr}` needs more than two registers"));
+        };
+        let script = Self { instructions: code.0, mode };
+        script.validate()?;
+        Ok(script)
+    }
+
+    fn validate(&self) -> Result<(), ScriptError> {
+        if self.instructions.len() > MAX_INSTRUCTIONS {
+            let n = self.instructions.len();
+            return error(format!("{n} instructions, at most {MAX_INSTRUCTIONS} fit"));
+        }
+        let unavailable = self.instructions.iter().find_map(
+            |&(Instr::And(x, _) | Instr::Or(x, _) | Instr::Not(x, _))| match x {
+                Reg::Sensor(s) if s as usize >= self.mode.sensors() => Some(x),
+                _ => None,
+            },
+        );
+        match unavailable {
+            Some(x) => error(format!("sensor `{x}` is not available in {:?} mode", self.mode)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the droid jumps, given what its sensors see. T and J start out false.
+    pub fn jumps(&self, sensors: &[bool]) -> bool {
+        let (mut t, mut j) = (false, false);
+        for &instruction in &self.instructions {
+            let get = |r, t, j| match r {
+                Reg::Sensor(s) => sensors[s as usize],
+                Reg::T => t,
+                Reg::J => j,
+            };
+            let (x, y) = match instruction {
+                Instr::And(x, y) | Instr::Or(x, y) | Instr::Not(x, y) => (get(x, t, j), y),
+            };
+            let v = match instruction {
+                Instr::And(..) => x && get(y, t, j),
+                Instr::Or(..) => x || get(y, t, j),
+                Instr::Not(..) => !x,
+            };
+            if y == Reg::T {
+                t = v;
+            } else {
+                j = v;
+            }
+        }
+        j
+    }
+
+    /// Walks the droid across `hull`, where `#` is ground and `.` a hole, starting on the first
+    /// tile. Past the end there is only ground. Returns where the droid falls, if it does.
+    pub fn simulate(&self, hull: &str) -> Result<(), usize> {
+        let ground = |x: usize| hull.as_bytes().get(x) != Some(&b'.');
+        let mut x = 0;
+        while x < hull.len() {
+            let sensors = (1..=self.mode.sensors()).map(|d| ground(x + d)).collect_vec();
+            x += if self.jumps(&sensors) { 4 } else { 1 };
+            if !ground(x) {
+                return Err(x);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A boolean expression over the sensors `A` to `I`, with `!`, `&`, `|` and parentheses. `&`
+/// binds tighter than `|`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum Expr {
+    Sensor(u8),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sensor(s) => write!(f, "{}", Reg::Sensor(*s)),
+            Self::Not(e) if matches!(**e, Self::Sensor(_)) => write!(f, "!{e}"),
+            Self::Not(e) => write!(f, "!({e})"),
+            Self::And(es) => {
+                let group = |e: &Self| match e {
+                    Self::Or(_) => format!("({e})"),
+                    _ => e.to_string(),
+                };
+                write!(f, "{}", es.iter().map(group).join(" & "))
+            }
+            Self::Or(es) => write!(f, "{}", es.iter().join(" | ")),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = s.chars().filter(|c| !c.is_whitespace()).collect_vec();
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => error(format!("unexpected `{c}` in `{s}`")),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [char],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn list(
+        &mut self,
+        op: char,
+        operand: fn(&mut Self) -> Result<Expr, ScriptError>,
+        build: fn(Vec<Expr>) -> Expr,
+    ) -> Result<Expr, ScriptError> {
+        let mut operands = vec![operand(self)?];
+        while self.peek() == Some(op) {
+            self.pos += 1;
+            operands.push(operand(self)?);
+        }
+        Ok(if operands.len() == 1 { operands.pop().unwrap() } else { build(operands) })
+    }
+
+    fn or(&mut self) -> Result<Expr, ScriptError> {
+        self.list('|', Self::and, |es| Expr::Or(flatten(es, false)))
+    }
+
+    fn and(&mut self) -> Result<Expr, ScriptError> {
+        self.list('&', Self::atom, |es| Expr::And(flatten(es, true)))
+    }
+
+    fn atom(&mut self) -> Result<Expr, ScriptError> {
+        let c = self.peek();
+        self.pos += 1;
+        match c {
+            Some('!') => Ok(not(self.atom()?)),
+            Some('(') => {
+                let expr = self.or()?;
+                if self.peek() != Some(')') {
+                    return error("missing `)`");
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(c @ 'A'..='I') => Ok(Expr::Sensor(c as u8 - b'A')),
+            Some(c) => error(format!("unexpected `{c}`")),
+            None => error("unexpected end of expression"),
+        }
+    }
+}
+
+/// Merges nested operands of the same operator, so that `A & (B & C)` becomes `A & B & C`.
+fn flatten(es: Vec<Expr>, and: bool) -> Vec<Expr> {
+    es.into_iter()
+        .flat_map(|e| match e {
+            Expr::And(es) if and => es,
+            Expr::Or(es) if !and => es,
+            e => vec![e],
+        })
+        .collect()
+}
+
+fn not(e: Expr) -> Expr {
+    match e {
+        Expr::Not(e) => *e,
+        e => Expr::Not(Box::new(e)),
+    }
+}
+
+/// A sensor, possibly negated.
+fn literal(e: &Expr) -> Option<(Reg, bool)> {
+    match e {
+        Expr::Sensor(s) => Some((Reg::Sensor(*s), true)),
+        Expr::Not(e) => match **e {
+            Expr::Sensor(s) => Some((Reg::Sensor(s), false)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// T and J, which both start out false.
+type Clear = [bool; 2];
+
+fn slot(r: Reg) -> usize {
+    (r == Reg::J) as usize
+}
+
+type Code = (Vec<Instr>, Clear);
+
+fn shortest(a: Option<Code>, b: Option<Code>) -> Option<Code> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.0.len() < a.0.len() { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+fn then(code: Option<Code>, instructions: impl FnOnce(Clear) -> Code) -> Option<Code> {
+    let (mut code, clear) = code?;
+    let (more, clear) = instructions(clear);
+    code.extend(more);
+    Some((code, clear))
+}
+
+/// Code that leaves the value of `e` in `r`, using `other` as scratch if given. Tries several
+/// equivalent forms of `e` and keeps the shortest.
+fn gen(e: &Expr, r: Reg, other: Option<Reg>, clear: Clear, dual: bool) -> Option<Code> {
+    let mut written = clear;
+    written[slot(r)] = false;
+    if let Some((x, positive)) = literal(e) {
+        let code = match (positive, clear[slot(r)]) {
+            (true, true) => vec![Instr::Or(x, r)],
+            (true, false) => vec![Instr::Not(x, r), Instr::Not(r, r)],
+            (false, _) => vec![Instr::Not(x, r)],
+        };
+        return Some((code, written));
+    }
+    match e {
+        Expr::Not(inner) => {
+            let negated = then(gen(inner, r, other, clear, true), |c| (vec![Instr::Not(r, r)], c));
+            let pushed = match &**inner {
+                Expr::And(es) => gen_list(
+                    false,
+                    &es.iter().cloned().map(not).collect_vec(),
+                    r,
+                    other,
+                    clear,
+                    false,
+                ),
+                Expr::Or(es) => gen_list(
+                    true,
+                    &es.iter().cloned().map(not).collect_vec(),
+                    r,
+                    other,
+                    clear,
+                    false,
+                ),
+                _ => None,
+            };
+            shortest(negated, pushed)
+        }
+        Expr::And(es) => gen_list(true, es, r, other, clear, dual),
+        Expr::Or(es) => gen_list(false, es, r, other, clear, dual),
+        Expr::Sensor(_) => unreachable!(),
+    }
+}
+
+/// Code for the conjunction (or disjunction, if not `and`) of `es`. One operand is computed in
+/// `r`, and the others are folded into it one by one.
+fn gen_list(
+    and: bool,
+    es: &[Expr],
+    r: Reg,
+    other: Option<Reg>,
+    clear: Clear,
+    dual: bool,
+) -> Option<Code> {
+    let op = |x, y| if and { Instr::And(x, y) } else { Instr::Or(x, y) };
+    let dual_op = |x, y| if and { Instr::Or(x, y) } else { Instr::And(x, y) };
+    let mut best = None;
+    for (i, first) in es.iter().enumerate() {
+        let mut code = gen(first, r, other, clear, true);
+        for e in es.iter().take(i).chain(es.iter().skip(i + 1)) {
+            code = match (literal(e), other) {
+                (Some((x, true)), _) => then(code, |c| (vec![op(x, r)], c)),
+                (Some((x, false)), Some(o)) => then(code, |mut c| {
+                    c[slot(o)] = false;
+                    (vec![Instr::Not(x, o), op(o, r)], c)
+                }),
+                // By De Morgan, r & !x is !(!r | x), and r | !x is !(!r & x).
+                (Some((x, false)), None) => {
+                    then(code, |c| (vec![Instr::Not(r, r), dual_op(x, r), Instr::Not(r, r)], c))
+                }
+                (None, Some(o)) => code.and_then(|(mut code, clear)| {
+                    let (more, clear) = gen(e, o, None, clear, true)?;
+                    code.extend(more);
+                    code.push(op(o, r));
+                    Some((code, clear))
+                }),
+                (None, None) => None,
+            };
+        }
+        best = shortest(best, code);
+    }
+    if dual {
+        let negated = es.iter().cloned().map(not).collect_vec();
+        let code = gen_list(!and, &negated, r, other, clear, false);
+        best = shortest(best, then(code, |c| (vec![Instr::Not(r, r)], c)));
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_sensors(n: usize) -> impl Iterator<Item = Vec<bool>> {
+        (0..1 << n).map(move |bits: usize| (0..n).map(|i| bits >> i & 1 == 1).collect())
+    }
+
+    fn eval(e: &Expr, sensors: &[bool]) -> bool {
+        match e {
+            Expr::Sensor(s) => sensors[*s as usize],
+            Expr::Not(e) => !eval(e, sensors),
+            Expr::And(es) => es.iter().all(|e| eval(e, sensors)),
+            Expr::Or(es) => es.iter().any(|e| eval(e, sensors)),
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        for (src, len) in [
+            ("D", 1),
+            ("!A", 1),
+            ("!A | !C & D", 4),
+            ("(!A | !B | !C) & D", 5),
+            ("(!A | !B | !C) & D & (H | E & (F | I))", 10),
+            ("(A | B) & (C | D) | E & !F", 8),
+            ("!(A & !(B | !C))", 4),
+        ] {
+            let expr: Expr = src.parse().unwrap();
+            let script = Script::compile(src, Mode::Run).unwrap();
+            assert_eq!(script.instructions.len(), len, "{src}:\n{script}");
+            for sensors in all_sensors(9) {
+                assert_eq!(script.jumps(&sensors), eval(&expr, &sensors), "{src}:\n{script}");
+            }
+        }
+        assert_eq!(
+            Script::compile("!A | !C & D", Mode::Walk).unwrap().to_string(),
+            "NOT A J\nNOT C T\nAND D T\nOR T J\nWALK\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let e = |src, mode| Script::compile(src, mode).unwrap_err().msg;
+        assert_eq!(e("A & E", Mode::Walk), "sensor `E` is not available in Walk mode");
+        assert_eq!(e("A & (B", Mode::Run), "missing `)`");
+        assert_eq!(e("A B", Mode::Run), "unexpected `B` in `A B`");
+        assert_eq!(
+            e("(A | B) & (C | D) | (E | F) & (G | H)", Mode::Run),
+            "`(A | B) & (C | D) | (E | F) & (G | H)` needs more than two registers"
+        );
+        let long = "A & !B | C & !D | E & !F | G & !H | I & !A | B & !C";
+        assert!(e(long, Mode::Run).ends_with("at most 15 fit"), "{}", e(long, Mode::Run));
+    }
+
+    #[test]
+    fn test_simulate() {
+        let script: Script = "NOT C J\nAND D J\nNOT A T\nOR T J\nWALK\n".parse().unwrap();
+        assert_eq!(script, Script::compile("!C & D | !A", Mode::Walk).unwrap());
+        for hull in ["#####.###########", "#####...#########", "#####..#.########"] {
+            assert_eq!(script.simulate(hull), Ok(()), "{hull}");
+        }
+        // Jumps over the first hole early, then has to jump again and lands in another one.
+        assert_eq!(script.simulate("#####.#.##..#.###"), Err(10));
+        assert!("NOT A X\nWALK".parse::<Script>().is_err());
+        assert!("NOT A J".parse::<Script>().is_err());
+    }
+}
@@ -1,13 +1,271 @@
-use std::io::Write;
+use nix::sys::termios::{self, LocalFlags, SetArg, SpecialCharacterIndices, Termios};
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::fd::AsFd;
 use std::sync::LazyLock;
 use terminfo::{capability, Database};
 
-static INFO: LazyLock<Database> = LazyLock::new(|| Database::from_env().unwrap());
+static BACKEND: LazyLock<Backend> = LazyLock::new(Backend::from_env);
 
-pub fn clear(mut term: impl Write) {
-    INFO.get::<capability::ClearScreen>().unwrap().expand().to(&mut term).unwrap();
+/// How escape sequences are produced: from the terminfo database, as plain ANSI sequences when
+/// `TERM` has no database entry, or not at all when there is no `TERM` to speak of.
+enum Backend {
+    Terminfo(Database),
+    Ansi,
+    Plain,
 }
 
-pub fn home(mut term: impl Write) {
-    INFO.get::<capability::CursorHome>().unwrap().expand().to(&mut term).unwrap();
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Command {
+    Clear,
+    Home,
+    MoveTo(usize, usize),
+    HideCursor,
+    ShowCursor,
+    EnterAlternateScreen,
+    LeaveAlternateScreen,
+    Foreground(Color),
+    Background(Color),
+    ResetColors,
+}
+
+impl Command {
+    fn terminfo(self, info: &Database, term: &mut dyn Write) -> Option<terminfo::Result<()>> {
+        use capability::*;
+        match self {
+            Self::Clear => info.get::<ClearScreen>().map(|c| c.expand().to(term)),
+            Self::Home => info.get::<CursorHome>().map(|c| c.expand().to(term)),
+            Self::MoveTo(row, col) => info
+                .get::<CursorAddress>()
+                .map(|c| c.expand().parameters(row as u32, col as u32).to(term)),
+            Self::HideCursor => info.get::<CursorInvisible>().map(|c| c.expand().to(term)),
+            Self::ShowCursor => info.get::<CursorNormal>().map(|c| c.expand().to(term)),
+            Self::EnterAlternateScreen => info.get::<EnterCaMode>().map(|c| c.expand().to(term)),
+            Self::LeaveAlternateScreen => info.get::<ExitCaMode>().map(|c| c.expand().to(term)),
+            Self::Foreground(color) => {
+                info.get::<SetAForeground>().map(|c| c.expand().parameters(color as u8).to(term))
+            }
+            Self::Background(color) => {
+                info.get::<SetABackground>().map(|c| c.expand().parameters(color as u8).to(term))
+            }
+            Self::ResetColors => info.get::<OrigPair>().map(|c| c.expand().to(term)),
+        }
+    }
+
+    fn ansi(self) -> String {
+        match self {
+            Self::Clear => "\x1b[H\x1b[2J".to_string(),
+            Self::Home => "\x1b[H".to_string(),
+            Self::MoveTo(row, col) => format!("\x1b[{};{}H", row + 1, col + 1),
+            Self::HideCursor => "\x1b[?25l".to_string(),
+            Self::ShowCursor => "\x1b[?25h".to_string(),
+            Self::EnterAlternateScreen => "\x1b[?1049h".to_string(),
+            Self::LeaveAlternateScreen => "\x1b[?1049l".to_string(),
+            Self::Foreground(color) => format!("\x1b[3{}m", color as u8),
+            Self::Background(color) => format!("\x1b[4{}m", color as u8),
+            Self::ResetColors => "\x1b[39;49m".to_string(),
+        }
+    }
+}
+
+impl Backend {
+    fn from_env() -> Self {
+        match Database::from_env() {
+            Ok(info) => Self::Terminfo(info),
+            Err(_) => match env::var("TERM") {
+                Ok(term) if !term.is_empty() && term != "dumb" => Self::Ansi,
+                _ => Self::Plain,
+            },
+        }
+    }
+
+    /// Writes `command` to `term`. Capabilities missing from the database are sent as ANSI.
+    fn emit(&self, command: Command, term: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Self::Terminfo(info) => match command.terminfo(info, term) {
+                Some(result) => result.map_err(io::Error::other),
+                None => term.write_all(command.ansi().as_bytes()),
+            },
+            Self::Ansi => term.write_all(command.ansi().as_bytes()),
+            Self::Plain => Ok(()),
+        }
+    }
+}
+
+fn emit(command: Command, mut term: impl Write) {
+    BACKEND.emit(command, &mut term).unwrap();
+}
+
+pub fn clear(term: impl Write) {
+    emit(Command::Clear, term);
+}
+
+pub fn home(term: impl Write) {
+    emit(Command::Home, term);
+}
+
+/// Moves the cursor to `row` and `col`, counting from 0.
+pub fn move_to(term: impl Write, row: usize, col: usize) {
+    emit(Command::MoveTo(row, col), term);
+}
+
+pub fn hide_cursor(term: impl Write) {
+    emit(Command::HideCursor, term);
+}
+
+pub fn show_cursor(term: impl Write) {
+    emit(Command::ShowCursor, term);
+}
+
+pub fn enter_alternate_screen(term: impl Write) {
+    emit(Command::EnterAlternateScreen, term);
+}
+
+pub fn leave_alternate_screen(term: impl Write) {
+    emit(Command::LeaveAlternateScreen, term);
+}
+
+pub fn foreground(term: impl Write, color: Color) {
+    emit(Command::Foreground(color), term);
+}
+
+pub fn background(term: impl Write, color: Color) {
+    emit(Command::Background(color), term);
+}
+
+pub fn reset_colors(term: impl Write) {
+    emit(Command::ResetColors, term);
+}
+
+/// Keeps a terminal in raw mode, where keys are read as they are pressed and not echoed, until
+/// dropped. Output processing and signals such as ^C are left alone.
+pub struct RawMode<T: AsFd> {
+    term: T,
+    saved: Termios,
+}
+
+impl<T: AsFd> RawMode<T> {
+    pub fn enable(term: T) -> io::Result<Self> {
+        let saved = termios::tcgetattr(&term)?;
+        let mut raw = saved.clone();
+        raw.local_flags.remove(LocalFlags::ICANON | LocalFlags::ECHO);
+        raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        termios::tcsetattr(&term, SetArg::TCSANOW, &raw)?;
+        Ok(Self { term, saved })
+    }
+}
+
+impl<T: AsFd> Drop for RawMode<T> {
+    fn drop(&mut self) {
+        let _ = termios::tcsetattr(&self.term, SetArg::TCSANOW, &self.saved);
+    }
+}
+
+impl<T: AsFd + Read> Read for RawMode<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.term.read(buf)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Esc,
+}
+
+/// Decodes key presses from raw terminal input. Keys that come in faster than they are asked
+/// for, like a held arrow key, arrive in a single read, so whatever follows the first key is kept
+/// for the next calls.
+pub struct Keys<R> {
+    input: R,
+    pending: Vec<u8>,
+}
+
+impl<R: Read> Keys<R> {
+    pub fn new(input: R) -> Self {
+        Self { input, pending: vec![] }
+    }
+
+    /// The next key press, or `None` at the end of input.
+    pub fn next_key(&mut self) -> io::Result<Option<Key>> {
+        if self.pending.is_empty() {
+            let mut buf = [0; 64];
+            let n = self.input.read(&mut buf)?;
+            self.pending.extend_from_slice(&buf[..n]);
+        }
+        let (key, len) = match self.pending[..] {
+            [] => return Ok(None),
+            [0x1b, b'[', b'A', ..] => (Key::Up, 3),
+            [0x1b, b'[', b'B', ..] => (Key::Down, 3),
+            [0x1b, b'[', b'C', ..] => (Key::Right, 3),
+            [0x1b, b'[', b'D', ..] => (Key::Left, 3),
+            [0x1b, ..] => (Key::Esc, 1),
+            _ => (1..=self.pending.len().min(4))
+                .find_map(|n| {
+                    let c = std::str::from_utf8(&self.pending[..n]).ok()?.chars().next()?;
+                    Some((Key::Char(c), n))
+                })
+                .unwrap_or((Key::Char(char::REPLACEMENT_CHARACTER), 1)),
+        };
+        self.pending.drain(..len);
+        Ok(Some(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(backend: &Backend, command: Command) -> String {
+        let mut out = vec![];
+        backend.emit(command, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_backends() {
+        assert_eq!(emitted(&Backend::Ansi, Command::MoveTo(2, 4)), "\x1b[3;5H");
+        assert_eq!(emitted(&Backend::Ansi, Command::Foreground(Color::Red)), "\x1b[31m");
+        assert_eq!(emitted(&Backend::Plain, Command::Clear), "");
+
+        let mut info = Database::new();
+        info.name("test").raw("clear", "CLS").raw("cup", "<%p1%d,%p2%d>");
+        let info = Backend::Terminfo(info.build().unwrap());
+        assert_eq!(emitted(&info, Command::Clear), "CLS");
+        assert_eq!(emitted(&info, Command::MoveTo(2, 4)), "<2,4>");
+        // Not in the database.
+        assert_eq!(emitted(&info, Command::HideCursor), "\x1b[?25l");
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut keys = Keys::new("\x1b[Aa".as_bytes());
+        assert_eq!(keys.next_key().unwrap(), Some(Key::Up));
+        assert_eq!(keys.next_key().unwrap(), Some(Key::Char('a')));
+        assert_eq!(keys.next_key().unwrap(), None);
+
+        let mut keys = Keys::new("\x1b[D\x1b[Cé\x1b".as_bytes());
+        assert_eq!(keys.next_key().unwrap(), Some(Key::Left));
+        assert_eq!(keys.next_key().unwrap(), Some(Key::Right));
+        assert_eq!(keys.next_key().unwrap(), Some(Key::Char('é')));
+        assert_eq!(keys.next_key().unwrap(), Some(Key::Esc));
+        assert_eq!(keys.next_key().unwrap(), None);
+    }
 }
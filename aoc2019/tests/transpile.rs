@@ -0,0 +1,76 @@
+use utils::Intcode;
+
+// The programs from the intcode.rs tests with the inputs they are tested on. Their transpiled
+// sources are checked in; run with UPDATE_TRANSPILED=1 to regenerate them.
+macro_rules! programs {
+    ($($name:ident: $program:expr, [$($input:expr),*];)*) => {
+        $(
+            mod $name {
+                include!(concat!("transpiled/", stringify!($name), ".rs"));
+            }
+        )*
+
+        type Transpiled = fn(Vec<isize>, &mut Vec<isize>);
+
+        const PROGRAMS: &[(&str, &str, &[&[isize]], Transpiled)] = &[$((
+            stringify!($name),
+            $program,
+            &[$(&$input),*],
+            |input, output| $name::run(input, |x| output.push(x)),
+        )),*];
+    };
+}
+
+programs! {
+    input_output: "3,9,1002,9,3,9,4,9,99,0", [[33]];
+    teq_position: "3,9,8,9,10,9,4,9,99,-1,8", [[1], [8]];
+    teq_immediate: "3,3,1108,-1,8,3,4,3,99", [[1], [8]];
+    tlt_position: "3,9,7,9,10,9,4,9,99,-1,8", [[7], [8]];
+    tlt_immediate: "3,3,1107,-1,8,3,4,3,99", [[-1], [10]];
+    jump_position: "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", [[0], [1]];
+    jump_immediate: "3,3,1105,-1,9,1101,0,0,12,4,12,99,1", [[0], [-1]];
+    large_program: "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,\
+                    125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        [[-1], [8], [10]];
+    quine: "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99", [[]];
+    bignum_mul: "1102,34915192,34915192,7,4,7,99,0", [[]];
+    bignum_output: "104,1125899906842624,99", [[]];
+    // Input 104 turns the halt at 4 into an output.
+    self_modifying: "3,4,104,7,99,0,99", [[99], [104]];
+}
+
+#[test]
+fn test_transpiled_sources() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/transpiled");
+    let update = std::env::var_os("UPDATE_TRANSPILED").is_some();
+    for &(name, program, _, _) in PROGRAMS {
+        let path = dir.join(format!("{name}.rs"));
+        let source = Intcode::parse(program).transpile();
+        if update {
+            std::fs::write(&path, &source).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), source, "{name} is out of date");
+    }
+}
+
+#[test]
+fn test_transpiled_outputs() {
+    for &(name, program, inputs, run) in PROGRAMS {
+        for input in inputs {
+            let mut output = vec![];
+            run(input.to_vec(), &mut output);
+            let expect: Vec<_> = Intcode::parse(program).run(input.iter().copied()).collect();
+            assert_eq!(output, expect, "{name} on {input:?}");
+        }
+    }
+}
+
+#[test]
+fn test_wide_addresses() {
+    // Addresses beyond usize are left to the generated code to reject, as the interpreter does.
+    let program: Intcode<i128> =
+        "1005,6,100000000000000000000000,4,100000000000000000000000,99,0".parse().unwrap();
+    let source = program.transpile();
+    assert!(source.contains("pc = addr(100000000000000000000000i128);"), "{source}");
+    assert!(source.contains("get(&mem, addr(100000000000000000000000i128))"), "{source}");
+}
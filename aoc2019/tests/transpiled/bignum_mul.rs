@@ -0,0 +1,39 @@
+// Transpiled from an Intcode program. Do not edit.
+
+#[allow(dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop)]
+pub fn run(input: impl IntoIterator<Item = isize>, mut output: impl FnMut(isize)) {
+    fn is_code(a: usize) -> bool {
+        matches!(a, 0..=6)
+    }
+    fn addr(a: isize) -> usize {
+        usize::try_from(a).expect("negative address")
+    }
+    fn get(mem: &utils::Memory<isize>, a: usize) -> isize {
+        mem[a]
+    }
+    fn set(mem: &mut utils::Memory<isize>, a: usize, v: isize) {
+        mem[a] = v;
+    }
+    let mut input = input.into_iter();
+    let mut mem: utils::Memory<isize> = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0].into();
+    let mut ra: isize = 0;
+    let mut pc: usize = 0;
+    loop {
+        match pc {
+            0 => {
+                // 0: mul 34915192, 34915192, [7]
+                let x: isize = 34915192isize;
+                let y: isize = 34915192isize;
+                set(&mut mem, 7, x.checked_mul(y).expect("overflow"));
+                // 4: out [7]
+                output(get(&mem, 7));
+                // 6: hlt
+                return;
+            }
+            _ => break,
+        }
+    }
+    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {
+        output(x);
+    }
+}
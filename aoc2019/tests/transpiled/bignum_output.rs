@@ -0,0 +1,35 @@
+// Transpiled from an Intcode program. Do not edit.
+
+#[allow(dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop)]
+pub fn run(input: impl IntoIterator<Item = isize>, mut output: impl FnMut(isize)) {
+    fn is_code(a: usize) -> bool {
+        matches!(a, 0..=2)
+    }
+    fn addr(a: isize) -> usize {
+        usize::try_from(a).expect("negative address")
+    }
+    fn get(mem: &utils::Memory<isize>, a: usize) -> isize {
+        mem[a]
+    }
+    fn set(mem: &mut utils::Memory<isize>, a: usize, v: isize) {
+        mem[a] = v;
+    }
+    let mut input = input.into_iter();
+    let mut mem: utils::Memory<isize> = vec![104, 1125899906842624, 99].into();
+    let mut ra: isize = 0;
+    let mut pc: usize = 0;
+    loop {
+        match pc {
+            0 => {
+                // 0: out 1125899906842624
+                output(1125899906842624isize);
+                // 2: hlt
+                return;
+            }
+            _ => break,
+        }
+    }
+    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {
+        output(x);
+    }
+}
@@ -0,0 +1,42 @@
+// Transpiled from an Intcode program. Do not edit.
+
+#[allow(dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop)]
+pub fn run(input: impl IntoIterator<Item = isize>, mut output: impl FnMut(isize)) {
+    fn is_code(a: usize) -> bool {
+        matches!(a, 0..=8)
+    }
+    fn addr(a: isize) -> usize {
+        usize::try_from(a).expect("negative address")
+    }
+    fn get(mem: &utils::Memory<isize>, a: usize) -> isize {
+        mem[a]
+    }
+    fn set(mem: &mut utils::Memory<isize>, a: usize, v: isize) {
+        mem[a] = v;
+    }
+    let mut input = input.into_iter();
+    let mut mem: utils::Memory<isize> = vec![3, 9, 1002, 9, 3, 9, 4, 9, 99, 0].into();
+    let mut ra: isize = 0;
+    let mut pc: usize = 0;
+    loop {
+        match pc {
+            0 => {
+                // 0: in [9]
+                let x = input.next().expect("input exhausted");
+                set(&mut mem, 9, x);
+                // 2: mul [9], 3, [9]
+                let x: isize = get(&mem, 9);
+                let y: isize = 3isize;
+                set(&mut mem, 9, x.checked_mul(y).expect("overflow"));
+                // 6: out [9]
+                output(get(&mem, 9));
+                // 8: hlt
+                return;
+            }
+            _ => break,
+        }
+    }
+    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {
+        output(x);
+    }
+}
@@ -0,0 +1,53 @@
+// Transpiled from an Intcode program. Do not edit.
+
+#[allow(dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop)]
+pub fn run(input: impl IntoIterator<Item = isize>, mut output: impl FnMut(isize)) {
+    fn is_code(a: usize) -> bool {
+        matches!(a, 0..=2 | 4..=11)
+    }
+    fn addr(a: isize) -> usize {
+        usize::try_from(a).expect("negative address")
+    }
+    fn get(mem: &utils::Memory<isize>, a: usize) -> isize {
+        mem[a]
+    }
+    fn set(mem: &mut utils::Memory<isize>, a: usize, v: isize) {
+        mem[a] = v;
+    }
+    let mut input = input.into_iter();
+    let mut mem: utils::Memory<isize> = vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1].into();
+    let mut ra: isize = 0;
+    let mut pc: usize = 0;
+    loop {
+        match pc {
+            0 => {
+                // 0: in [3]
+                let x = input.next().expect("input exhausted");
+                set(&mut mem, 3, x);
+                // 2: jit [3], 9
+                if get(&mem, 3) != 0 {
+                    pc = 9;
+                    continue;
+                }
+                pc = 5;
+            }
+            5 => {
+                // 5: add 0, 0, [12]
+                let x: isize = 0isize;
+                let y: isize = 0isize;
+                set(&mut mem, 12, x.checked_add(y).expect("overflow"));
+                pc = 9;
+            }
+            9 => {
+                // 9: out [12]
+                output(get(&mem, 12));
+                // 11: hlt
+                return;
+            }
+            _ => break,
+        }
+    }
+    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {
+        output(x);
+    }
+}
@@ -0,0 +1,50 @@
+// Transpiled from an Intcode program. Do not edit.
+
+#[allow(dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop)]
+pub fn run(input: impl IntoIterator<Item = isize>, mut output: impl FnMut(isize)) {
+    fn is_code(a: usize) -> bool {
+        matches!(a, 0..=11)
+    }
+    fn addr(a: isize) -> usize {
+        usize::try_from(a).expect("negative address")
+    }
+    fn get(mem: &utils::Memory<isize>, a: usize) -> isize {
+        mem[a]
+    }
+    fn set(mem: &mut utils::Memory<isize>, a: usize, v: isize) {
+        mem[a] = v;
+    }
+    let mut input = input.into_iter();
+    let mut mem: utils::Memory<isize> = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9].into();
+    let mut ra: isize = 0;
+    let mut pc: usize = 0;
+    loop {
+        match pc {
+            0 => {
+                // 0: in [12]
+                let x = input.next().expect("input exhausted");
+                set(&mut mem, 12, x);
+                // 2: jif [12], [15]
+                if get(&mem, 12) == 0 {
+                    pc = addr(get(&mem, 15));
+                    continue;
+                }
+                pc = 5;
+            }
+            5 => {
+                // 5: add [13], [14], [13]
+                let x: isize = get(&mem, 13);
+                let y: isize = get(&mem, 14);
+                set(&mut mem, 13, x.checked_add(y).expect("overflow"));
+                // 9: out [13]
+                output(get(&mem, 13));
+                // 11: hlt
+                return;
+            }
+            _ => break,
+        }
+    }
+    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {
+        output(x);
+    }
+}
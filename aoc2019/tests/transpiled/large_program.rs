@@ -0,0 +1,94 @@
+// Transpiled from an Intcode program. Do not edit.
+
+#[allow(dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop)]
+pub fn run(input: impl IntoIterator<Item = isize>, mut output: impl FnMut(isize)) {
+    fn is_code(a: usize) -> bool {
+        matches!(a, 0..=18 | 22..=44 | 46..=46)
+    }
+    fn addr(a: isize) -> usize {
+        usize::try_from(a).expect("negative address")
+    }
+    fn get(mem: &utils::Memory<isize>, a: usize) -> isize {
+        mem[a]
+    }
+    fn set(mem: &mut utils::Memory<isize>, a: usize, v: isize) {
+        mem[a] = v;
+    }
+    let mut input = input.into_iter();
+    let mut mem: utils::Memory<isize> = vec![3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99].into();
+    let mut ra: isize = 0;
+    let mut pc: usize = 0;
+    loop {
+        match pc {
+            0 => {
+                // 0: in [21]
+                let x = input.next().expect("input exhausted");
+                set(&mut mem, 21, x);
+                // 2: eq [21], 8, [20]
+                let x: isize = get(&mem, 21);
+                let y: isize = 8isize;
+                set(&mut mem, 20, (x == y) as isize);
+                // 6: jit [20], 22
+                if get(&mem, 20) != 0 {
+                    pc = 22;
+                    continue;
+                }
+                pc = 9;
+            }
+            9 => {
+                // 9: lt 8, [21], [20]
+                let x: isize = 8isize;
+                let y: isize = get(&mem, 21);
+                set(&mut mem, 20, (x < y) as isize);
+                // 13: jif [20], 31
+                if get(&mem, 20) == 0 {
+                    pc = 31;
+                    continue;
+                }
+                pc = 16;
+            }
+            16 => {
+                // 16: jif 0, 36
+                pc = 36;
+                continue;
+            }
+            22 => {
+                // 22: mul [21], 125, [20]
+                let x: isize = get(&mem, 21);
+                let y: isize = 125isize;
+                set(&mut mem, 20, x.checked_mul(y).expect("overflow"));
+                // 26: out [20]
+                output(get(&mem, 20));
+                // 28: jit 1, 46
+                pc = 46;
+                continue;
+            }
+            31 => {
+                // 31: out 999
+                output(999isize);
+                // 33: jit 1, 46
+                pc = 46;
+                continue;
+            }
+            36 => {
+                // 36: add 1000, 1, [20]
+                let x: isize = 1000isize;
+                let y: isize = 1isize;
+                set(&mut mem, 20, x.checked_add(y).expect("overflow"));
+                // 40: out [20]
+                output(get(&mem, 20));
+                // 42: jit 1, 46
+                pc = 46;
+                continue;
+            }
+            46 => {
+                // 46: hlt
+                return;
+            }
+            _ => break,
+        }
+    }
+    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {
+        output(x);
+    }
+}
@@ -0,0 +1,53 @@
+// Transpiled from an Intcode program. Do not edit.
+
+#[allow(dead_code, unused_mut, clippy::never_loop, clippy::while_let_loop)]
+pub fn run(input: impl IntoIterator<Item = isize>, mut output: impl FnMut(isize)) {
+    fn is_code(a: usize) -> bool {
+        matches!(a, 0..=15)
+    }
+    fn addr(a: isize) -> usize {
+        usize::try_from(a).expect("negative address")
+    }
+    fn get(mem: &utils::Memory<isize>, a: usize) -> isize {
+        mem[a]
+    }
+    fn set(mem: &mut utils::Memory<isize>, a: usize, v: isize) {
+        mem[a] = v;
+    }
+    let mut input = input.into_iter();
+    let mut mem: utils::Memory<isize> = vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99].into();
+    let mut ra: isize = 0;
+    let mut pc: usize = 0;
+    loop {
+        match pc {
+            0 => {
+                // 0: arb 1
+                ra = ra.checked_add(1isize).expect("overflow");
+                // 2: out [ra-1]
+                output(get(&mem, addr(ra.checked_add(-1).expect("overflow"))));
+                // 4: add [100], 1, [100]
+                let x: isize = get(&mem, 100);
+                let y: isize = 1isize;
+                set(&mut mem, 100, x.checked_add(y).expect("overflow"));
+                // 8: eq [100], 16, [101]
+                let x: isize = get(&mem, 100);
+                let y: isize = 16isize;
+                set(&mut mem, 101, (x == y) as isize);
+                // 12: jif [101], 0
+                if get(&mem, 101) == 0 {
+                    pc = 0;
+                    continue;
+                }
+                pc = 15;
+            }
+            15 => {
+                // 15: hlt
+                return;
+            }
+            _ => break,
+        }
+    }
+    for x in utils::Intcode::from_parts(mem, pc, ra).run(input) {
+        output(x);
+    }
+}